@@ -0,0 +1,106 @@
+use crate::interval::{self, Interval};
+use crate::ray::Ray;
+use crate::vector::{Point3, Vec3};
+
+// axis-aligned bounding box
+#[derive(Copy, Clone)]
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        let mut bbox = Self { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    pub fn from_points(a: Point3, b: Point3) -> Self {
+        // treat the two points a and b as extrema for the bounding box, in any order
+        Self::new(
+            Interval::new(a.x().min(b.x()), a.x().max(b.x())),
+            Interval::new(a.y().min(b.y()), a.y().max(b.y())),
+            Interval::new(a.z().min(b.z()), a.z().max(b.z())),
+        )
+    }
+
+    pub fn around_disk(center: Point3, normal: Vec3, radius: f64) -> Self {
+        // along each axis the rim of a disk reaches out by the radius times the sine of the angle
+        // between the axis and the unit normal
+        let extent = |n: f64| radius * (1.0 - n * n).max(0.0).sqrt();
+        let rvec = Vec3::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+        Self::from_points(center - rvec, center + rvec)
+    }
+
+    pub fn surrounding(a: &Aabb, b: &Aabb) -> Self {
+        Self {
+            x: Interval::enclosing(&a.x, &b.x),
+            y: Interval::enclosing(&a.y, &b.y),
+            z: Interval::enclosing(&a.z, &b.z),
+        }
+    }
+
+    pub fn axis_interval(&self, n: usize) -> &Interval {
+        match n {
+            1 => &self.y,
+            2 => &self.z,
+            _ => &self.x,
+        }
+    }
+
+    pub fn hit(&self, r: &Ray, ray_t: Interval) -> bool {
+        self.clip(r, ray_t).is_some()
+    }
+
+    pub fn clip(&self, r: &Ray, mut ray_t: Interval) -> Option<Interval> {
+        // slab test: narrow ray_t down to where the ray is inside all three slabs
+        let origin = r.origin();
+        let direction = r.direction();
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let adinv = 1.0 / direction[axis];
+
+            let t0 = (ax.min - origin[axis]) * adinv;
+            let t1 = (ax.max - origin[axis]) * adinv;
+
+            ray_t.min = ray_t.min.max(t0.min(t1));
+            ray_t.max = ray_t.max.min(t0.max(t1));
+
+            if ray_t.max <= ray_t.min {
+                return None;
+            }
+        }
+
+        Some(ray_t)
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        // index of the longest axis of the bounding box
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    fn pad_to_minimums(&mut self) {
+        // keep flat boxes, like those of axis-aligned triangles, from having zero thickness
+        let delta = 0.0001;
+        if self.x.size() < delta {
+            self.x = self.x.expand(delta);
+        }
+        if self.y.size() < delta {
+            self.y = self.y.expand(delta);
+        }
+        if self.z.size() < delta {
+            self.z = self.z.expand(delta);
+        }
+    }
+}
+
+pub const EMPTY: Aabb = Aabb { x: interval::EMPTY, y: interval::EMPTY, z: interval::EMPTY };
@@ -0,0 +1,86 @@
+use std::cmp::Ordering;
+
+use crate::aabb::{self, Aabb};
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+// bounding volume hierarchy: a binary tree of boxes that lets a ray skip every object whose box
+// it misses. a node with a single object has no right child
+pub struct BvhNode {
+    left: Box<dyn Hittable>,
+    right: Option<Box<dyn Hittable>>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    pub fn new(list: HittableList) -> Self {
+        Self::from_objects(list.into_objects())
+    }
+
+    pub fn from_objects(mut objects: Vec<Box<dyn Hittable>>) -> Self {
+        // split along the axis the objects are most spread out on
+        let mut bbox = aabb::EMPTY;
+        for object in objects.iter() {
+            bbox = Aabb::surrounding(&bbox, &object.bounding_box());
+        }
+        let axis = bbox.longest_axis();
+
+        match objects.len() {
+            0 => panic!("Could not build a BVH from an empty list."),
+            1 => Self { left: objects.pop().unwrap(), right: None, bbox },
+            2 => {
+                let right = objects.pop().unwrap();
+                Self { left: objects.pop().unwrap(), right: Some(right), bbox }
+            }
+            _ => {
+                objects.sort_by(|a, b| Self::box_compare(a.as_ref(), b.as_ref(), axis));
+
+                let rest = objects.split_off(objects.len() / 2);
+                Self {
+                    left: Box::new(Self::from_objects(objects)),
+                    right: Some(Box::new(Self::from_objects(rest))),
+                    bbox,
+                }
+            }
+        }
+    }
+
+    fn box_compare(a: &dyn Hittable, b: &dyn Hittable, axis: usize) -> Ordering {
+        let a_axis_interval = a.bounding_box().axis_interval(axis).min;
+        let b_axis_interval = b.bounding_box().axis_interval(axis).min;
+        a_axis_interval.total_cmp(&b_axis_interval)
+    }
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(r, ray_t) {
+            return None;
+        }
+
+        // anything on the right has to be closer than what was found on the left
+        let hit_left = self.left.hit(r, ray_t);
+        let max = hit_left.as_ref().map_or(ray_t.max, |record| record.t);
+        let hit_right = self.right.as_ref().and_then(|right| right.hit(r, Interval::new(ray_t.min, max)));
+
+        hit_right.or(hit_left)
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        if !self.bbox.hit(r, ray_t) {
+            return 1.0;
+        }
+
+        let left = self.left.transmittance(r, ray_t);
+        if left == 0.0 {
+            return 0.0;
+        }
+        left * self.right.as_ref().map_or(1.0, |right| right.transmittance(r, ray_t))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
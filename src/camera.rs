@@ -1,16 +1,46 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{self, Write};
+use std::path::Path;
+use std::time::Instant;
 
-use crate::common::{degrees_to_radians, random_f64, INFINITY};
+use crate::checkpoint::RenderProgress;
+use crate::common::{degrees_to_radians, hash, seed_random, INFINITY};
+use crate::film::{Film, PixelStats};
+use crate::filter::FilterType;
 use crate::hittable::Hittable;
 use crate::interval::Interval;
 use crate::ray::Ray;
-use crate::vector::{self, cross, random_in_unit_disk, unit_vector, Color, Point3, Vec3};
+use crate::sampler::{Sampler, SamplerType};
+use crate::spectrum::{uplift, SampledSpectrum, SampledWavelengths};
+use crate::vector::{self, cross, sample_unit_disk, unit_vector, Color, Point3, Vec3};
 use chrono;
 
+// number of samples taken between convergence tests when sampling adaptively
+const ADAPTIVE_BATCH: i32 = 8;
+
+// samples per pixel in each pass of a budgeted render that isn't progressive
+const BUDGET_PASS_SAMPLES: i32 = 4;
+
 pub struct Camera {
     pub aspect_ratio: f64,
     pub image_width: i32,
+    pub adaptive_threshold: f64, // stop sampling a pixel once its estimated error drops below this (0 disables)
+    pub min_samples_per_pixel: i32, // samples taken before a pixel may stop early
+    pub write_sample_map: bool, // also write an image of the per-pixel sample counts
+    pub sampler: SamplerType, // how sample points are chosen for pixels, the lens and BSDFs
+    pub filter: FilterType, // pixel reconstruction filter
+    pub filter_radius: Option<f64>, // filter radius in pixels, or the filter's default
+    pub progressive_samples: i32, // samples per pixel per progressive pass (0 renders in a single pass)
+    pub snapshot_interval: Option<f64>, // seconds between progressive snapshots, or after every pass
+    pub seed: u64, // base seed for all the random numbers drawn while rendering
+    pub checkpoint_file: Option<String>, // where render progress is periodically saved
+    pub checkpoint_interval: f64, // seconds between checkpoints
+    pub resume: bool, // continue the render saved in checkpoint_file
+    pub time_budget: Option<f64>, // stop once this many seconds have been spent rendering
+    pub sample_budget: Option<i64>, // stop once this many samples have been taken over the whole image
+    pub target_noise: Option<f64>, // stop once the average estimated pixel error drops below this
+    pub spectral: bool, // trace wavelengths instead of RGB, for dispersion and more accurate metals
+    pub background: Option<Color>, // flat color seen by rays that escape the scene, instead of the sky
 
     samples_per_pixel: i32,
     max_depth: i32,
@@ -21,7 +51,6 @@ pub struct Camera {
     defocus_angle: f64,
     focus_dist: f64,
     image_height: i32,
-    pixel_samples_scale: f64, // color scale factor for a  sum of pixel samples
     center: Point3,
     pixel00_loc: Point3,
     pixel_delta_u: Vec3,
@@ -46,6 +75,23 @@ impl Camera {
         Self {
             aspect_ratio,
             image_width,
+            adaptive_threshold: 0.0,
+            min_samples_per_pixel: 16,
+            write_sample_map: false,
+            sampler: SamplerType::Independent,
+            filter: FilterType::Box,
+            filter_radius: None,
+            progressive_samples: 0,
+            snapshot_interval: None,
+            seed: 0,
+            checkpoint_file: None,
+            checkpoint_interval: 600.0,
+            resume: false,
+            time_budget: None,
+            sample_budget: None,
+            target_noise: None,
+            spectral: false,
+            background: None,
 
             samples_per_pixel,
             max_depth,
@@ -56,7 +102,6 @@ impl Camera {
             defocus_angle,
             focus_dist,
             image_height: 0,
-            pixel_samples_scale: 1.0 / samples_per_pixel as f64,
             center: Point3::new(0.0, 0.0, 0.0),
             pixel00_loc: Point3::new(0.0, 0.0, 0.0),
             pixel_delta_u: Vec3::new(0.0, 0.0, 0.0),
@@ -66,51 +111,337 @@ impl Camera {
         }
     }
 
-    pub fn render(&mut self, world: impl Hittable) {
+    pub fn render(&mut self, world: impl Hittable) -> io::Result<()> {
         self.initialize();
 
+        let pass_samples = self.pass_samples();
+        let mut progress = self.start_progress(pass_samples)?;
+        let stop_reason = self.render_passes(&world, &mut progress, pass_samples, |_| true);
+
+        progress.film.write_ppm(&progress.filename);
+
+        // the render is finished, so there is nothing left to resume; keeping the checkpoint
+        // would make running the same command again "resume" a finished image
+        if let Some(path) = self.checkpoint_file.as_ref().filter(|path| Path::new(path).exists()) {
+            fs::remove_file(path)?;
+        }
+
+        let sample_counts: Vec<i32> = progress.pixel_stats.iter().map(|stats| stats.count).collect();
+        let total_samples: i64 = sample_counts.iter().map(|&n| n as i64).sum();
+        println!("\rDone ({}).", stop_reason);
+        println!(
+            "{} passes, {} samples ({:.1} per pixel) in {:.1}s, estimated noise {:.4}",
+            progress.pass - 1,
+            total_samples,
+            total_samples as f64 / sample_counts.len() as f64,
+            progress.elapsed,
+            Self::image_noise(&progress.pixel_stats)
+        );
+
+        if self.write_sample_map {
+            let map_filename = progress.filename.replace(".ppm", "-samples.ppm");
+            self.write_sample_counts(&map_filename, &sample_counts);
+        }
+
+        Ok(())
+    }
+
+    fn pass_samples(&self) -> i32 {
+        // in progressive mode every pass adds up to progressive_samples to each pixel and the image
+        // is written out between passes. budgeted renders also work in passes so they can stop at
+        // any pass with an evenly sampled image; otherwise a single pass takes all the samples
+        let budgeted = self.time_budget.is_some() || self.sample_budget.is_some() || self.target_noise.is_some();
+        if self.progressive_samples > 0 {
+            self.progressive_samples
+        } else if budgeted {
+            BUDGET_PASS_SAMPLES
+        } else {
+            self.samples_per_pixel
+        }
+    }
+
+    fn filter_radius(&self) -> f64 {
+        self.filter_radius.unwrap_or(self.filter.default_radius())
+    }
+
+    fn start_progress(&self, pass_samples: i32) -> io::Result<RenderProgress> {
+        // the checkpoint being resumed, or a fresh render
+        if self.resume {
+            if let Some(path) = self.checkpoint_file.as_ref().filter(|path| Path::new(path).exists()) {
+                let progress = RenderProgress::load(path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("Could not read checkpoint {}: {}", path, e)))?;
+
+                // anything that changes what a sample adds to the film has to match, or the sums
+                // from before and after resuming wouldn't belong together
+                let mut changed = Vec::new();
+                if progress.film.width() != self.image_width || progress.film.height() != self.image_height {
+                    changed.push("image size");
+                }
+                if progress.samples_per_pixel != self.samples_per_pixel {
+                    changed.push("samples per pixel");
+                }
+                if progress.pass_samples != pass_samples {
+                    changed.push("samples per pass");
+                }
+                if progress.seed != self.seed {
+                    changed.push("seed");
+                }
+                if progress.sampler != self.sampler {
+                    changed.push("sampler");
+                }
+                if progress.filter != self.filter {
+                    changed.push("filter");
+                }
+                if progress.filter_radius != self.filter_radius() {
+                    changed.push("filter radius");
+                }
+                if progress.spectral != self.spectral {
+                    changed.push("spectral rendering");
+                }
+                if !changed.is_empty() {
+                    let message = format!("Checkpoint was made with different render settings: {}.", changed.join(", "));
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+                }
+
+                println!("Resuming {} from pass {}, scanline {}", progress.filename, progress.pass, progress.row);
+                return Ok(progress);
+            }
+
+            match &self.checkpoint_file {
+                Some(path) => println!("No checkpoint found at {}, starting a new render.", path),
+                None => println!("No checkpoint file to resume from, starting a new render."),
+            }
+        }
+
         // let mut stdout = std::io::stdout();
         let datetime = chrono::Local::now().format("%Y-%m-%d_%H-%M");
-        let filename = format!("test-{}.ppm", datetime);
-        let mut file = File::create(filename).expect("Could not create file.");
-
-        let header = std::format!("P6\n{} {}\n255\n", self.image_width, self.image_height);
+        Ok(RenderProgress {
+            filename: format!("test-{}.ppm", datetime),
+            samples_per_pixel: self.samples_per_pixel,
+            pass_samples,
+            seed: self.seed,
+            sampler: self.sampler,
+            filter: self.filter,
+            filter_radius: self.filter_radius(),
+            spectral: self.spectral,
+            pass: 1,
+            row: 0,
+            current_pass_samples: 0,
+            elapsed: 0.0,
+            film: Film::new(self.image_width, self.image_height),
+            pixel_stats: vec![PixelStats::new(); (self.image_width * self.image_height) as usize],
+        })
+    }
 
-        // stdout.write_all(header_bytes);
-        file.write_all(header.as_bytes())
-            .expect("Could not write to file.");
+    fn render_passes(
+        &self,
+        world: &dyn Hittable,
+        progress: &mut RenderProgress,
+        pass_samples: i32,
+        mut keep_going: impl FnMut(&RenderProgress) -> bool,
+    ) -> &'static str {
+        // renders passes until a budget runs out or every pixel is done, and says which it was.
+        // `keep_going` is asked before every scanline, and stops the render where it is if it
+        // returns false
+        let mut sampler = self.sampler.create(self.samples_per_pixel);
+        let filter = self.filter.create(self.filter_radius());
+        let start = Instant::now();
+        let elapsed_before = progress.elapsed;
+        let mut last_snapshot = Instant::now();
+        let mut last_checkpoint = Instant::now();
+        let stop_reason;
+
+        loop {
+            // a new pass is planned before its first scanline; a resumed pass keeps its plan
+            if progress.row == 0 {
+                progress.elapsed = elapsed_before + start.elapsed().as_secs_f64();
+                match self.plan_pass(progress, pass_samples) {
+                    Ok(samples) => progress.current_pass_samples = samples,
+                    Err(reason) => {
+                        stop_reason = reason;
+                        break;
+                    }
+                }
+            }
 
-        // stdout.flush();
-        for height in 0..self.image_height {
-            print!("\rScanlines remaining: {:04}", self.image_height - height);
-            io::stdout().flush();
+            // stdout.flush();
+            for height in progress.row..self.image_height {
+                if !keep_going(progress) {
+                    progress.row = height;
+                    return "interrupted";
+                }
 
-            for width in 0..self.image_width {
-                // let color = Self::ray_color(r, world);
-                // let pixel_center = self.pixel00_loc + (width as f64 * self.pixel_delta_u) + (height as f64 * self.pixel_delta_v);
-                // let ray_direction = pixel_center - self.center;
+                if let Some(path) = &self.checkpoint_file {
+                    if last_checkpoint.elapsed().as_secs_f64() >= self.checkpoint_interval {
+                        progress.row = height;
+                        progress.elapsed = elapsed_before + start.elapsed().as_secs_f64();
+                        progress.save(path).expect("Could not write checkpoint.");
+                        last_checkpoint = Instant::now();
+                    }
+                }
 
-                // let r = Ray::new(self.center, ray_direction);
+                // a timed snapshot is also written partway through a pass, so that long passes
+                // still show up every snapshot_interval seconds
+                let snapshot_due = self.snapshot_interval.is_some_and(|seconds| last_snapshot.elapsed().as_secs_f64() >= seconds);
+                if self.progressive_samples > 0 && snapshot_due {
+                    progress.film.write_ppm(&progress.filename);
+                    last_snapshot = Instant::now();
+                }
 
-                // let color = Self::ray_color(r, &world);
+                if pass_samples < self.samples_per_pixel {
+                    print!("\rPass {}, scanlines remaining: {:04}", progress.pass, self.image_height - height);
+                } else {
+                    print!("\rScanlines remaining: {:04}", self.image_height - height);
+                }
+                io::stdout().flush();
+
+                for width in 0..self.image_width {
+                    // let color = Self::ray_color(r, world);
+                    // let pixel_center = self.pixel00_loc + (width as f64 * self.pixel_delta_u) + (height as f64 * self.pixel_delta_v);
+                    // let ray_direction = pixel_center - self.center;
+
+                    // let r = Ray::new(self.center, ray_direction);
+
+                    // let color = Self::ray_color(r, &world);
+
+                    let pixel_index = height * self.image_width + width;
+                    let stats = &mut progress.pixel_stats[pixel_index as usize];
+                    if stats.done {
+                        continue;
+                    }
+
+                    let pass_end = (stats.count + progress.current_pass_samples).min(self.samples_per_pixel);
+                    while stats.count < pass_end {
+                        // every sample gets its own random sequence, so resuming from a checkpoint
+                        // reproduces an uninterrupted render exactly
+                        seed_random(hash(self.seed ^ hash(((pixel_index as u64) << 32) | stats.count as u64)));
+                        sampler.start_pixel_sample(width, height, stats.count);
+
+                        // samples are spread over the filter's whole support, not just the pixel
+                        let offset = 2.0 * filter.radius() * Self::sample_square(sampler.as_mut());
+                        let r = self.get_ray(width, height, offset, sampler.as_mut());
+                        let color = if self.spectral {
+                            let mut lambda = SampledWavelengths::sample_uniform(sampler.get_1d());
+                            let radiance = self.ray_color_spectral(r, world, self.max_depth, &mut lambda, sampler.as_mut());
+                            lambda.to_rgb(radiance)
+                        } else {
+                            self.ray_color(r, world, self.max_depth, sampler.as_mut())
+                        };
+
+                        progress.film.add_sample(width as f64 + offset.x(), height as f64 + offset.y(), color, filter.as_ref());
+                        stats.add(color);
+
+                        if self.pixel_converged(stats) {
+                            stats.done = true;
+                            break;
+                        }
+                    }
+
+                    if stats.count >= self.samples_per_pixel {
+                        stats.done = true;
+                    }
+                }
+            }
 
-                let mut color = Color::new(0.0, 0.0, 0.0);
-                for _ in 0..self.samples_per_pixel {
-                    let r = self.get_ray(width, height);
-                    color = color + Self::ray_color(r, &world, self.max_depth);
+            progress.pass += 1;
+            progress.row = 0;
+
+            if self.progressive_samples > 0 {
+                let snapshot_due = match self.snapshot_interval {
+                    Some(seconds) => last_snapshot.elapsed().as_secs_f64() >= seconds,
+                    None => true,
+                };
+                if snapshot_due {
+                    progress.film.write_ppm(&progress.filename);
+                    last_snapshot = Instant::now();
                 }
+            }
+        }
+
+        stop_reason
+    }
 
-                color = self.pixel_samples_scale * color;
-                color.write(&mut file);
+    fn plan_pass(&self, progress: &RenderProgress, pass_samples: i32) -> Result<i32, &'static str> {
+        // decides how many samples each unfinished pixel gets in the next pass, shrinking the pass
+        // to spend whatever is left of the budgets evenly, or explains why rendering should stop
+        let active_pixels = progress.pixel_stats.iter().filter(|stats| !stats.done).count() as i64;
+        if active_pixels == 0 {
+            return Err("all pixels finished");
+        }
+
+        if let Some(target) = self.target_noise {
+            if Self::image_noise(&progress.pixel_stats) <= target {
+                return Err("target noise reached");
+            }
+        }
+
+        let total_samples: i64 = progress.pixel_stats.iter().map(|stats| stats.count as i64).sum();
+        let mut samples = pass_samples as i64;
+
+        if let Some(budget) = self.sample_budget {
+            samples = samples.min((budget - total_samples) / active_pixels);
+            if samples < 1 {
+                return Err("sample budget spent");
             }
         }
+
+        // the time a pass will take is estimated from the average cost of a sample so far
+        if let Some(budget) = self.time_budget {
+            if total_samples > 0 {
+                let seconds_per_sample = progress.elapsed / total_samples as f64;
+                let affordable = (budget - progress.elapsed) / seconds_per_sample;
+                samples = samples.min((affordable / active_pixels as f64) as i64);
+            }
+            if samples < 1 || progress.elapsed >= budget {
+                return Err("time budget spent");
+            }
+        }
+
+        Ok(samples as i32)
+    }
+
+    fn image_noise(pixel_stats: &[PixelStats]) -> f64 {
+        // average estimated error over the pixels that have enough samples to estimate it
+        let errors: Vec<f64> = pixel_stats.iter().filter(|stats| stats.count > 1).map(|stats| stats.error()).collect();
+        if errors.is_empty() {
+            return INFINITY;
+        }
+
+        errors.iter().sum::<f64>() / errors.len() as f64
+    }
+
+    fn pixel_converged(&self, stats: &PixelStats) -> bool {
+        // only test for convergence every ADAPTIVE_BATCH samples, once the minimum has been taken
+        if self.adaptive_threshold <= 0.0
+            || stats.count < self.min_samples_per_pixel
+            || stats.count % ADAPTIVE_BATCH != 0
+        {
+            return false;
+        }
+
+        stats.error() < self.adaptive_threshold
+    }
+
+    fn write_sample_counts(&self, filename: &str, sample_counts: &[i32]) {
+        // grayscale map where white is samples_per_pixel and black is no samples
+        let mut file = File::create(filename).expect("Could not create file.");
+        let header = std::format!("P5\n{} {}\n255\n", self.image_width, self.image_height);
+        file.write_all(header.as_bytes())
+            .expect("Could not write to file.");
+
+        let bytes: Vec<u8> = sample_counts
+            .iter()
+            .map(|&n| (255.0 * n as f64 / self.samples_per_pixel as f64) as u8)
+            .collect();
+        file.write_all(&bytes).expect("Could not write to file.");
     }
 
     fn initialize(&mut self) {
         let image_height = (self.image_width as f64 / self.aspect_ratio) as i32;
         self.image_height = if image_height < 1 { 1 } else { image_height };
 
-        self.pixel_samples_scale = 1.0 / self.samples_per_pixel as f64;
+        assert!(self.samples_per_pixel >= 1, "Samples per pixel must be at least 1, not {}.", self.samples_per_pixel);
+        self.min_samples_per_pixel = self.min_samples_per_pixel.clamp(1, self.samples_per_pixel);
 
         // self.center = Point3::new(0.0, 0.0, 0.0);
         self.center = self.lookfrom;
@@ -151,7 +482,7 @@ impl Camera {
         self.defocus_disk_v = v * defocus_radius; 
     }
 
-    fn ray_color(r: Ray, world: &dyn Hittable, depth: i32) -> Color {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, depth: i32, sampler: &mut dyn Sampler) -> Color {
         // if we've hit the max_depth, no more light is gathered
         if depth <= 0 {
             return Color::new(0.0, 0.0, 0.0);
@@ -168,27 +499,68 @@ impl Camera {
             // return 0.5 * Self::ray_color(Ray::new(record.p, direction), world, depth-1);
 
             if record.mat.is_some() {
-                let maybe_scattered = record.mat.unwrap().scatter(r, &record);
+                let mat = record.mat.unwrap();
+                let color_from_emission = mat.emitted(record.u, record.v, &record.p);
+
+                let maybe_scattered = mat.scatter(r, &record, sampler);
                 if maybe_scattered.is_some() {
                     let (attenuation, scattered) = maybe_scattered.unwrap();
+                    let scattered = scattered.leaving(record.surface);
 
-                    return attenuation * Self::ray_color(scattered, world, depth - 1);
+                    return color_from_emission + attenuation * self.ray_color(scattered, world, depth - 1, sampler);
                 }
 
-                return Color::new(0.0, 0.0, 0.0);
+                return color_from_emission;
             }
         }
 
+        self.background(r)
+    }
+
+    fn ray_color_spectral(
+        &self,
+        r: Ray,
+        world: &dyn Hittable,
+        depth: i32,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> SampledSpectrum {
+        // ray_color, carrying radiance at the path's wavelengths instead of in RGB
+        if depth <= 0 {
+            return SampledSpectrum::constant(0.0);
+        }
+
+        let Some(record) = world.hit(&r, Interval::new(0.001, INFINITY)) else {
+            return uplift(self.background(r), lambda);
+        };
+        let Some(mat) = record.mat else {
+            return uplift(self.background(r), lambda);
+        };
+
+        let emission = mat.emitted_spectral(record.u, record.v, &record.p, lambda);
+        match mat.scatter_spectral(r, &record, lambda, sampler) {
+            Some((attenuation, scattered)) => {
+                let scattered = scattered.leaving(record.surface);
+                emission + attenuation * self.ray_color_spectral(scattered, world, depth - 1, lambda, sampler)
+            }
+            None => emission,
+        }
+    }
+
+    fn background(&self, r: Ray) -> Color {
+        if let Some(color) = self.background {
+            return color;
+        }
+
         let unit_direction = vector::unit_vector(r.direction());
         let t = 0.5 * (unit_direction.y() + 1.0);
         (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
     }
 
-    fn get_ray(&self, i: i32, j: i32) -> Ray {
-        // construct a camera ray originating from the defocus disk and directed at randomly sampled
-        // points around the pixel location i, j
+    fn get_ray(&self, i: i32, j: i32, offset: Vec3, sampler: &mut dyn Sampler) -> Ray {
+        // construct a camera ray originating from the defocus disk and directed at the point
+        // `offset` away from the pixel location i, j
 
-        let offset = Self::sample_square();
         let pixel_sample: Vec3 = self.pixel00_loc
             + ((i as f64 + offset.x()) * self.pixel_delta_u)
             + ((j as f64 + offset.y()) * self.pixel_delta_v);
@@ -197,21 +569,102 @@ impl Camera {
         let ray_origin = if self.defocus_angle <= 0.0 {
             self.center
         } else {
-            self.defocus_disk_sample()
+            self.defocus_disk_sample(sampler)
         };
         let ray_direction = pixel_sample - ray_origin;
 
         Ray::new(ray_origin, ray_direction)
     }
 
-    fn sample_square() -> Vec3 {
+    fn sample_square(sampler: &mut dyn Sampler) -> Vec3 {
         // returns the vector to  a random point in the [-.5, -.5] - [.5, .5] unit square
-        Vec3::new(random_f64() - 0.5, random_f64() - 0.5, 0.0)
+        let (u, v) = sampler.get_2d();
+        Vec3::new(u - 0.5, v - 0.5, 0.0)
     }
 
-    fn defocus_disk_sample(&self) -> Point3 {
+    fn defocus_disk_sample(&self, sampler: &mut dyn Sampler) -> Point3 {
         // returns a random point in the camera defeocus disk
-        let p = random_in_unit_disk();
+        let p = sample_unit_disk(sampler.get_2d());
         return self.center + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    use crate::hittable_list::HittableList;
+    use crate::material::{Lambertian, Metal};
+    use crate::sphere::Sphere;
+
+    fn tiny_scene() -> (HittableList, Camera) {
+        let mut world = HittableList::new();
+        world.add(Sphere::new(Point3::new(0.0, -100.5, -1.0), 100.0, Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        world.add(Sphere::new(Point3::new(0.0, 0.0, -1.0), 0.5, Metal::new(Color::new(0.8, 0.6, 0.2), 0.3)));
+
+        let mut camera = Camera::new(
+            16.0 / 9.0,
+            32,
+            8,
+            10,
+            90.0,
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            0.0,
+            1.0,
+        );
+        camera.seed = 7;
+        // a budget that never runs out, just to render in passes without writing snapshots
+        camera.sample_budget = Some(i64::MAX);
+        camera.initialize();
+
+        (world, camera)
+    }
+
+    fn film_bytes(film: &Film) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        film.save(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted() {
+        let path = env::temp_dir().join(format!("raytracing-resume-test-{}.ckpt", process::id()));
+        let path = path.to_str().unwrap().to_string();
+
+        let (world, mut camera) = tiny_scene();
+        let pass_samples = camera.pass_samples();
+        assert!(pass_samples < camera.samples_per_pixel, "the test render should take more than one pass");
+
+        let mut straight = camera.start_progress(pass_samples).unwrap();
+        camera.render_passes(&world, &mut straight, pass_samples, |_| true);
+
+        // checkpointed before every scanline and stopped partway through the second pass. the
+        // scanline in progress when it stopped is lost, as it would be if the process were killed
+        camera.checkpoint_file = Some(path.clone());
+        camera.checkpoint_interval = 0.0;
+        let mut interrupted = camera.start_progress(pass_samples).unwrap();
+        let mut scanlines = 0;
+        let stop = camera.image_height + camera.image_height / 2;
+        camera.render_passes(&world, &mut interrupted, pass_samples, |_| {
+            scanlines += 1;
+            scanlines <= stop
+        });
+        drop(interrupted);
+
+        camera.resume = true;
+        let mut resumed = camera.start_progress(pass_samples).unwrap();
+        assert_eq!(resumed.pass, 2);
+        camera.render_passes(&world, &mut resumed, pass_samples, |_| true);
+
+        // resuming with a sample-affecting setting changed is refused rather than mixing films
+        camera.seed += 1;
+        let error = camera.start_progress(pass_samples).err().expect("resumed with a different seed");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        let _ = fs::remove_file(&path);
+
+        assert!(film_bytes(&straight.film) == film_bytes(&resumed.film), "resumed render differs from the uninterrupted one");
+    }
+}
@@ -0,0 +1,131 @@
+use crate::aabb::Aabb;
+use crate::common::{solve_quadratic, INFINITY, PI};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{dot, Point3, Vec3};
+
+#[derive(Copy, Clone)]
+enum Part {
+    Side,
+    Bottom,
+    Top,
+}
+
+// every point within `radius` of the segment from a to b: a cylinder with hemispherical ends. u
+// runs around the axis and v along the outline, from the pole at a to the pole at b
+pub struct Capsule {
+    a: Point3,
+    height: f64,
+    radius: f64,
+    frame: Onb,
+    mat: Box<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(a: Point3, b: Point3, radius: f64, mat: impl Material + 'static) -> Self {
+        // everything is worked out in a frame where the axis runs up z from a
+        let axis = b - a;
+        Self { a, height: axis.length(), radius, frame: Onb::new(axis), mat: Box::new(mat) }
+    }
+
+    fn surface(&self, p: Point3, part: Part) -> (Vec3, (f64, f64), (Vec3, Vec3)) {
+        // outward normal, texture coordinates and tangents at a local point on the given part
+        let phi = p.y().atan2(p.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let dpdu = 2.0 * PI * Vec3::new(-p.y(), p.x(), 0.0);
+
+        // v is the distance along the outline, over its whole length
+        let length = self.height + PI * self.radius;
+        let quarter = 0.5 * PI * self.radius;
+
+        let center = match part {
+            Part::Side => {
+                let normal = Vec3::new(p.x(), p.y(), 0.0) / self.radius;
+                let v = (quarter + p.z()) / length;
+                return (normal, (phi / (2.0 * PI), v), (dpdu, Vec3::new(0.0, 0.0, length)));
+            }
+            Part::Bottom => Point3::new(0.0, 0.0, 0.0),
+            Part::Top => Point3::new(0.0, 0.0, self.height),
+        };
+
+        let normal = (p - center) / self.radius;
+        let elevation = normal.z().clamp(-1.0, 1.0).asin();
+        let start = if let Part::Top = part { quarter + self.height } else { quarter };
+        let arc = start + self.radius * elevation;
+        let dpdv = length * Vec3::new(-elevation.sin() * phi.cos(), -elevation.sin() * phi.sin(), elevation.cos());
+
+        (normal, (phi / (2.0 * PI), arc / length), (dpdu, dpdv))
+    }
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let o = self.frame.to_local(r.origin() - self.a);
+        let d = self.frame.to_local(r.direction());
+
+        let mut candidates = [(INFINITY, Part::Side); 6];
+        let mut count = 0;
+
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let z = o.z() + t * d.z();
+                if (0.0..=self.height).contains(&z) {
+                    candidates[count] = (t, Part::Side);
+                    count += 1;
+                }
+            }
+        }
+
+        // each end is the half of a sphere beyond the side
+        for (z, part) in [(0.0, Part::Bottom), (self.height, Part::Top)] {
+            let oc = o - Vec3::new(0.0, 0.0, z);
+            let c = dot(&oc, &oc) - self.radius * self.radius;
+            if let Some((t0, t1)) = solve_quadratic(dot(&d, &d), 2.0 * dot(&oc, &d), c) {
+                for t in [t0, t1] {
+                    let beyond = oc.z() + t * d.z();
+                    let outside = if let Part::Top = part { beyond > 0.0 } else { beyond < 0.0 };
+                    if outside {
+                        candidates[count] = (t, part);
+                        count += 1;
+                    }
+                }
+            }
+        }
+
+        // the nearest hit that a cutout material doesn't let the ray through
+        candidates[..count].sort_by(|a, b| a.0.total_cmp(&b.0));
+        for &(t, part) in &candidates[..count] {
+            if !ray_t.surrounds(t) {
+                continue;
+            }
+
+            let (normal, uv, (dpdu, dpdv)) = self.surface(o + t * d, part);
+
+            let mut rec = HitRecord::new();
+            rec.t = t;
+            rec.p = r.at(t);
+            rec.set_face_normal(r, &self.frame.to_world(normal));
+            (rec.u, rec.v) = uv;
+            (rec.dpdu, rec.dpdv) = (self.frame.to_world(dpdu), self.frame.to_world(dpdv));
+            rec.mat = Some(&(*self.mat));
+
+            if rec.is_opaque(r) {
+                return Some(rec);
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let b = self.a + self.height * self.frame.to_world(Vec3::new(0.0, 0.0, 1.0));
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::surrounding(&Aabb::from_points(self.a - rvec, self.a + rvec), &Aabb::from_points(b - rvec, b + rvec))
+    }
+}
@@ -0,0 +1,155 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use crate::film::{Film, PixelStats};
+use crate::filter::FilterType;
+use crate::sampler::SamplerType;
+
+const MAGIC: &[u8; 8] = b"RTCKPT02";
+
+// everything needed to pick a render up exactly where it left off. randomness is reseeded from
+// `seed` for every pixel sample, so the seed is all the RNG state there is to keep
+pub struct RenderProgress {
+    pub filename: String,
+    pub samples_per_pixel: i32,
+    pub pass_samples: i32,
+    pub seed: u64,
+    pub sampler: SamplerType,
+    pub filter: FilterType,
+    pub filter_radius: f64,
+    pub spectral: bool,
+    pub pass: i32,
+    pub row: i32, // next scanline of the current pass
+    pub current_pass_samples: i32, // samples per pixel in the current pass, which budgets can shorten
+    pub elapsed: f64, // seconds spent rendering so far
+    pub film: Film,
+    pub pixel_stats: Vec<PixelStats>,
+}
+
+impl RenderProgress {
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        // written beside the old checkpoint and renamed over it, so being killed mid-write keeps
+        // the previous checkpoint intact
+        let temp_path = format!("{}.tmp", path);
+        let mut w = BufWriter::new(File::create(&temp_path)?);
+
+        w.write_all(MAGIC)?;
+        write_string(&mut w, &self.filename)?;
+        write_i32(&mut w, self.samples_per_pixel)?;
+        write_i32(&mut w, self.pass_samples)?;
+        write_u64(&mut w, self.seed)?;
+        write_string(&mut w, &format!("{:?}", self.sampler).to_lowercase())?;
+        write_string(&mut w, &format!("{:?}", self.filter).to_lowercase())?;
+        write_f64(&mut w, self.filter_radius)?;
+        write_i32(&mut w, self.spectral as i32)?;
+        write_i32(&mut w, self.pass)?;
+        write_i32(&mut w, self.row)?;
+        write_i32(&mut w, self.current_pass_samples)?;
+        write_f64(&mut w, self.elapsed)?;
+
+        self.film.save(&mut w)?;
+        for stats in self.pixel_stats.iter() {
+            write_f64(&mut w, stats.luminance_sum)?;
+            write_f64(&mut w, stats.luminance_sq_sum)?;
+            write_i32(&mut w, stats.count)?;
+            write_i32(&mut w, stats.done as i32)?;
+        }
+
+        w.flush()?;
+        drop(w);
+        fs::rename(&temp_path, path)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let mut r = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a render checkpoint"));
+        }
+
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let filename = read_string(&mut r)?;
+        let samples_per_pixel = read_i32(&mut r)?;
+        let pass_samples = read_i32(&mut r)?;
+        let seed = read_u64(&mut r)?;
+        let sampler = read_string(&mut r)?.parse().map_err(invalid)?;
+        let filter = read_string(&mut r)?.parse().map_err(invalid)?;
+        let filter_radius = read_f64(&mut r)?;
+        let spectral = read_i32(&mut r)? != 0;
+        let pass = read_i32(&mut r)?;
+        let row = read_i32(&mut r)?;
+        let current_pass_samples = read_i32(&mut r)?;
+        let elapsed = read_f64(&mut r)?;
+
+        let film = Film::load(&mut r)?;
+        let mut pixel_stats = Vec::with_capacity((film.width() * film.height()) as usize);
+        for _ in 0..film.width() * film.height() {
+            pixel_stats.push(PixelStats {
+                luminance_sum: read_f64(&mut r)?,
+                luminance_sq_sum: read_f64(&mut r)?,
+                count: read_i32(&mut r)?,
+                done: read_i32(&mut r)? != 0,
+            });
+        }
+
+        Ok(Self {
+            filename,
+            samples_per_pixel,
+            pass_samples,
+            seed,
+            sampler,
+            filter,
+            filter_radius,
+            spectral,
+            pass,
+            row,
+            current_pass_samples,
+            elapsed,
+            film,
+            pixel_stats,
+        })
+    }
+}
+
+pub fn write_f64(w: &mut impl Write, x: f64) -> io::Result<()> {
+    w.write_all(&x.to_le_bytes())
+}
+
+pub fn write_i32(w: &mut impl Write, x: i32) -> io::Result<()> {
+    w.write_all(&x.to_le_bytes())
+}
+
+pub fn write_u64(w: &mut impl Write, x: u64) -> io::Result<()> {
+    w.write_all(&x.to_le_bytes())
+}
+
+fn write_string(w: &mut impl Write, s: &str) -> io::Result<()> {
+    write_u64(w, s.len() as u64)?;
+    w.write_all(s.as_bytes())
+}
+
+pub fn read_f64(r: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(f64::from_le_bytes(bytes))
+}
+
+pub fn read_i32(r: &mut impl Read) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+pub fn read_u64(r: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    r.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_string(r: &mut impl Read) -> io::Result<String> {
+    let mut bytes = vec![0; read_u64(r)? as usize];
+    r.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
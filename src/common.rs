@@ -1,4 +1,7 @@
-use rand::random;
+use std::cell::RefCell;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
 
 pub const INFINITY: f64 = f64::INFINITY;
 pub const PI: f64 = 3.1415926535897932385;
@@ -8,8 +11,50 @@ pub fn degrees_to_radians(degrees: f64) -> f64 {
     degrees * PI / 180.0
 }
 
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
 pub fn random_f64() -> f64 {
-    random::<f64>()
+    RNG.with(|rng| rng.borrow_mut().gen::<f64>())
+}
+
+pub fn seed_random(seed: u64) {
+    // restart the random number sequence, so that everything drawn afterwards is reproducible
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    // real roots of a t^2 + b t + c = 0 in increasing order, computed without the cancellation
+    // the textbook formula suffers when b^2 is much larger than 4ac. a linear equation's one
+    // root is returned twice
+    if a.abs() < 1e-12 {
+        if b == 0.0 {
+            return None;
+        }
+        return Some((-c / b, -c / b));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return Some((0.0, 0.0));
+    }
+
+    let (t0, t1) = (q / a, c / q);
+    Some((t0.min(t1), t0.max(t1)))
+}
+
+pub fn hash(x: u64) -> u64 {
+    // splitmix64 finalizer
+    let mut z = x.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
 }
 
 pub fn random_range_f64(min: f64, max: f64) -> f64{
@@ -0,0 +1,117 @@
+use crate::aabb::Aabb;
+use crate::common::{solve_quadratic, INFINITY, PI};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{unit_vector, Point3, Vec3};
+
+// cone narrowing from a circular base of `radius` to a point at `apex`, optionally closed off with
+// a flat base. on the side u runs around the axis and v from base to apex; the base is mapped like
+// a disk
+pub struct Cone {
+    base: Point3,
+    height: f64,
+    radius: f64,
+    capped: bool,
+    frame: Onb,
+    mat: Box<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(base: Point3, apex: Point3, radius: f64, capped: bool, mat: impl Material + 'static) -> Self {
+        // everything is worked out in a frame where the axis runs up z from the base
+        let axis = apex - base;
+        Self { base, height: axis.length(), radius, capped, frame: Onb::new(axis), mat: Box::new(mat) }
+    }
+
+    fn surface(&self, p: Point3, side: bool) -> (Vec3, (f64, f64), (Vec3, Vec3)) {
+        // outward normal, texture coordinates and tangents at a local point on the side or base
+        let phi = p.y().atan2(p.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let dpdu = 2.0 * PI * Vec3::new(-p.y(), p.x(), 0.0);
+        let distance = (p.x() * p.x() + p.y() * p.y()).sqrt();
+
+        if side {
+            // the gradient of x^2 + y^2 - (k (h - z))^2, where k is the slope of the side
+            let k = self.radius / self.height;
+            let normal = if distance < 1e-9 { Vec3::new(0.0, 0.0, 1.0) } else { unit_vector(Vec3::new(p.x(), p.y(), k * distance)) };
+            let dpdv = Vec3::new(-self.radius * phi.cos(), -self.radius * phi.sin(), self.height);
+            return (normal, (phi / (2.0 * PI), p.z() / self.height), (dpdu, dpdv));
+        }
+
+        // at the center the angle is degenerate; any tangent frame will do
+        let tangents = if distance < 1e-9 {
+            (Vec3::new(0.0, 1.0, 0.0), Vec3::new(self.radius, 0.0, 0.0))
+        } else {
+            (dpdu, self.radius / distance * Vec3::new(p.x(), p.y(), 0.0))
+        };
+        (Vec3::new(0.0, 0.0, -1.0), (phi / (2.0 * PI), distance / self.radius), tangents)
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let o = self.frame.to_local(r.origin() - self.base);
+        let d = self.frame.to_local(r.direction());
+
+        let mut candidates = [(INFINITY, true); 3];
+        let mut count = 0;
+
+        // the side is where x^2 + y^2 = (k (h - z))^2, below the apex
+        let k2 = (self.radius / self.height).powi(2);
+        let above = self.height - o.z();
+        let a = d.x() * d.x() + d.y() * d.y() - k2 * d.z() * d.z();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y() + k2 * above * d.z());
+        let c = o.x() * o.x() + o.y() * o.y() - k2 * above * above;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let z = o.z() + t * d.z();
+                if (0.0..=self.height).contains(&z) {
+                    candidates[count] = (t, true);
+                    count += 1;
+                }
+            }
+        }
+
+        if self.capped && d.z().abs() > 1e-12 {
+            let t = -o.z() / d.z();
+            let (x, y) = (o.x() + t * d.x(), o.y() + t * d.y());
+            if x * x + y * y <= self.radius * self.radius {
+                candidates[count] = (t, false);
+                count += 1;
+            }
+        }
+
+        // the nearest hit that a cutout material doesn't let the ray through
+        candidates[..count].sort_by(|a, b| a.0.total_cmp(&b.0));
+        for &(t, side) in &candidates[..count] {
+            if !ray_t.surrounds(t) {
+                continue;
+            }
+
+            let (normal, uv, (dpdu, dpdv)) = self.surface(o + t * d, side);
+
+            let mut rec = HitRecord::new();
+            rec.t = t;
+            rec.p = r.at(t);
+            rec.set_face_normal(r, &self.frame.to_world(normal));
+            (rec.u, rec.v) = uv;
+            (rec.dpdu, rec.dpdv) = (self.frame.to_world(dpdu), self.frame.to_world(dpdv));
+            rec.mat = Some(&(*self.mat));
+
+            if rec.is_opaque(r) {
+                return Some(rec);
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let axis = self.frame.to_world(Vec3::new(0.0, 0.0, 1.0));
+        let apex = self.base + self.height * axis;
+        Aabb::surrounding(&Aabb::around_disk(self.base, axis, self.radius), &Aabb::from_points(apex, apex))
+    }
+}
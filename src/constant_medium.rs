@@ -0,0 +1,82 @@
+use crate::aabb::Aabb;
+use crate::common::{random_f64, INFINITY};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::{self, Interval};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::Vec3;
+
+// fog, smoke or murky water of the same density throughout a closed boundary shape. rays are
+// scattered by the phase function after a random free-flight distance, or pass straight through
+pub struct ConstantMedium {
+    boundary: Box<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Box<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: impl Hittable + 'static, density: f64, phase_function: impl Material + 'static) -> Self {
+        Self {
+            boundary: Box::new(boundary),
+            neg_inv_density: -1.0 / density,
+            phase_function: Box::new(phase_function),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // where the ray's line enters and leaves the boundary, which may be behind the origin
+        // when the ray starts inside
+        let t_enter = self.boundary.hit(r, interval::UNIVERSE)?.t;
+        let t_exit = self.boundary.hit(r, Interval::new(t_enter + 0.0001, INFINITY))?.t;
+
+        let t_enter = t_enter.max(ray_t.min).max(0.0);
+        let t_exit = t_exit.min(ray_t.max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        // exponentially distributed distance to the next scattering event
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * random_f64().ln();
+        if hit_distance > distance_inside_boundary {
+            return None;
+        }
+
+        let mut rec = HitRecord::new();
+        rec.t = t_enter + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+
+        // a medium has no surface, so the normal is arbitrary
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.geometric_normal = rec.normal;
+        rec.front_face = true;
+        rec.mat = Some(&(*self.phase_function));
+
+        Some(rec)
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        // the density is constant, so the fraction that gets through needs no sampling
+        let Some(enter) = self.boundary.hit(r, interval::UNIVERSE) else {
+            return 1.0;
+        };
+        let Some(exit) = self.boundary.hit(r, Interval::new(enter.t + 0.0001, INFINITY)) else {
+            return 1.0;
+        };
+
+        let t_enter = enter.t.max(ray_t.min).max(0.0);
+        let t_exit = exit.t.min(ray_t.max);
+        if t_enter >= t_exit {
+            return 1.0;
+        }
+
+        ((t_exit - t_enter) * r.direction().length() / self.neg_inv_density).exp()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
@@ -0,0 +1,207 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitInterval, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+#[derive(Copy, Clone)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(&self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            Self::Union => in_a || in_b,
+            Self::Intersection => in_a && in_b,
+            Self::Difference => in_a && !in_b,
+        }
+    }
+}
+
+// constructive solid geometry: the union, intersection or difference of two closed objects,
+// found by combining the stretches of the ray inside each. surfaces keep the material of the
+// object they came from, so the walls of a hole cut with a difference have the material of the
+// object that cut it. csg nodes are closed objects too, and can be combined further
+pub struct Csg {
+    operation: CsgOperation,
+    a: Box<dyn Hittable>,
+    b: Box<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, a: impl Hittable + 'static, b: impl Hittable + 'static) -> Self {
+        let (box_a, box_b) = (a.bounding_box(), b.bounding_box());
+        let overlap = |x: &Interval, y: &Interval| Interval::new(x.min.max(y.min), x.max.min(y.max));
+
+        let bbox = match operation {
+            CsgOperation::Union => Aabb::surrounding(&box_a, &box_b),
+            CsgOperation::Intersection => {
+                Aabb::new(overlap(&box_a.x, &box_b.x), overlap(&box_a.y, &box_b.y), overlap(&box_a.z, &box_b.z))
+            }
+            CsgOperation::Difference => box_a,
+        };
+
+        Self { operation, a: Box::new(a), b: Box::new(b), bbox }
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        if !self.bbox.hit(r, ray_t) {
+            return None;
+        }
+
+        // the first boundary of the combined object within the interval, going in or out
+        self.hit_intervals(r, ray_t)
+            .into_iter()
+            .flat_map(|interval| [interval.enter, interval.exit])
+            .find(|record| ray_t.surrounds(record.t))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+
+    fn hit_intervals(&self, r: &Ray, ray_t: Interval) -> Vec<HitInterval<'_>> {
+        // sweep along the ray through every boundary of both objects in order, keeping track of
+        // whether the ray is inside each, and start or end an interval wherever that changes
+        // whether it is inside the result
+        let mut boundaries = Vec::new();
+        for (from_a, object) in [(true, &self.a), (false, &self.b)] {
+            for interval in object.hit_intervals(r, ray_t) {
+                boundaries.push((from_a, true, interval.enter));
+                boundaries.push((from_a, false, interval.exit));
+            }
+        }
+        boundaries.sort_by(|x, y| x.2.t.total_cmp(&y.2.t));
+
+        let mut intervals = Vec::new();
+        let (mut in_a, mut in_b) = (false, false);
+        let mut enter: Option<HitRecord> = None;
+
+        for (from_a, entering, mut record) in boundaries {
+            let was_inside = self.operation.inside(in_a, in_b);
+            if from_a {
+                in_a = entering;
+            } else {
+                in_b = entering;
+            }
+            let inside = self.operation.inside(in_a, in_b);
+
+            // normals always face the ray, so only which side of the result the ray is on can
+            // differ from the object the boundary came from
+            if inside && !was_inside {
+                record.front_face = true;
+                enter = Some(record);
+            } else if was_inside && !inside {
+                record.front_face = false;
+                // operands that both start or end at the same place can open and close the result
+                // there at once, which isn't a stretch of the ray at all
+                if let Some(enter) = enter.take().filter(|enter| enter.t < record.t) {
+                    intervals.push(HitInterval { enter, exit: record });
+                }
+            }
+        }
+
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::INFINITY;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vector::{Color, Point3, Vec3};
+
+    fn ball(x: f64, radius: f64) -> Sphere {
+        Sphere::new(Point3::new(x, 0.0, 0.0), radius, Lambertian::new(Color::new(1.0, 1.0, 1.0)))
+    }
+
+    // the stretches inside an object of a ray along the x axis from `start`, as x coordinates
+    fn inside(object: &dyn Hittable, start: f64) -> Vec<(f64, f64)> {
+        let r = Ray::new(Point3::new(start, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        object
+            .hit_intervals(&r, Interval::new(0.001, INFINITY))
+            .iter()
+            .map(|interval| (interval.enter.t + start, interval.exit.t + start))
+            .collect()
+    }
+
+    fn csg(operation: CsgOperation, a: (f64, f64), b: (f64, f64)) -> Csg {
+        Csg::new(operation, ball(a.0, a.1), ball(b.0, b.1))
+    }
+
+    // a spans [-2, 2]; overlapping spans [1, 5], disjoint [9, 11], nested [-0.5, 1.5] and touching [2, 6]
+    const A: (f64, f64) = (0.0, 2.0);
+    const OVERLAPPING: (f64, f64) = (3.0, 2.0);
+    const DISJOINT: (f64, f64) = (10.0, 1.0);
+    const NESTED: (f64, f64) = (0.5, 1.0);
+    const TOUCHING: (f64, f64) = (4.0, 2.0);
+
+    #[test]
+    fn overlapping_operands() {
+        assert_eq!(inside(&csg(CsgOperation::Union, A, OVERLAPPING), -10.0), vec![(-2.0, 5.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Intersection, A, OVERLAPPING), -10.0), vec![(1.0, 2.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, A, OVERLAPPING), -10.0), vec![(-2.0, 1.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, OVERLAPPING, A), -10.0), vec![(2.0, 5.0)]);
+    }
+
+    #[test]
+    fn disjoint_operands() {
+        assert_eq!(inside(&csg(CsgOperation::Union, A, DISJOINT), -10.0), vec![(-2.0, 2.0), (9.0, 11.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Intersection, A, DISJOINT), -10.0), vec![]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, A, DISJOINT), -10.0), vec![(-2.0, 2.0)]);
+    }
+
+    #[test]
+    fn nested_operands() {
+        assert_eq!(inside(&csg(CsgOperation::Union, A, NESTED), -10.0), vec![(-2.0, 2.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Intersection, A, NESTED), -10.0), vec![(-0.5, 1.5)]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, A, NESTED), -10.0), vec![(-2.0, -0.5), (1.5, 2.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, NESTED, A), -10.0), vec![]);
+    }
+
+    #[test]
+    fn touching_operands() {
+        // the union covers both without a gap, however the shared boundary is split
+        let union = inside(&csg(CsgOperation::Union, A, TOUCHING), -10.0);
+        assert_eq!((union[0].0, union[union.len() - 1].1), (-2.0, 6.0));
+        assert!(union.windows(2).all(|pair| pair[0].1 == pair[1].0));
+
+        assert_eq!(inside(&csg(CsgOperation::Intersection, A, TOUCHING), -10.0), vec![]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, A, TOUCHING), -10.0), vec![(-2.0, 2.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, TOUCHING, A), -10.0), vec![(2.0, 6.0)]);
+    }
+
+    #[test]
+    fn ray_starting_inside_an_operand() {
+        // from the middle of a, the stretch the ray starts in enters at minus infinity
+        assert_eq!(inside(&csg(CsgOperation::Union, A, OVERLAPPING), 0.0), vec![(-INFINITY, 5.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Intersection, A, OVERLAPPING), 0.0), vec![(1.0, 2.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, A, OVERLAPPING), 0.0), vec![(-INFINITY, 1.0)]);
+        assert_eq!(inside(&csg(CsgOperation::Difference, A, NESTED), 0.0), vec![(1.5, 2.0)]);
+
+        // so the first hit is on the way out, with the normal still facing the ray
+        let difference = csg(CsgOperation::Difference, A, OVERLAPPING);
+        let r = Ray::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let rec = difference.hit(&r, Interval::new(0.001, INFINITY)).expect("missed the way out");
+        assert_eq!(rec.t, 1.0);
+        assert!(!rec.front_face);
+        assert!(rec.normal.x() < 0.0);
+    }
+
+    #[test]
+    fn csg_nodes_combine_further() {
+        let carved = csg(CsgOperation::Difference, A, OVERLAPPING);
+        let both = Csg::new(CsgOperation::Union, carved, ball(DISJOINT.0, DISJOINT.1));
+        assert_eq!(inside(&both, -10.0), vec![(-2.0, 1.0), (9.0, 11.0)]);
+
+        let hollow = Csg::new(CsgOperation::Difference, csg(CsgOperation::Union, A, NESTED), ball(0.0, 1.0));
+        assert_eq!(inside(&hollow, -10.0), vec![(-2.0, -1.0), (1.0, 2.0)]);
+    }
+}
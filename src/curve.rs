@@ -0,0 +1,308 @@
+use std::f64::consts::SQRT_2;
+use std::rc::Rc;
+
+use crate::aabb::{self, Aabb};
+use crate::bvh::BvhNode;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{cross, dot, unit_vector, Point3, Vec3};
+
+// deepest the curve is split in half while looking for where a ray crosses it
+const MAX_CURVE_DEPTH: i32 = 10;
+
+// how many pieces each curve is cut into for the bvh, whose boxes hug a bent curve much more
+// tightly than one box around all of it
+const CURVE_SEGMENTS: usize = 8;
+
+#[derive(Copy, Clone)]
+pub enum CurveType {
+    // a flat ribbon, turned to face whichever ray is looking at it
+    Flat,
+    // the same ribbon, but shaded as though it were round
+    Cylinder,
+}
+
+// a cubic bézier curve through `points`, with its width changing evenly from one end to the other
+#[derive(Copy, Clone)]
+pub struct Curve {
+    pub points: [Point3; 4],
+    pub widths: (f64, f64),
+}
+
+impl Curve {
+    pub fn new(points: [Point3; 4], widths: (f64, f64)) -> Self {
+        Self { points, widths }
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        (1.0 - u) * self.widths.0 + u * self.widths.1
+    }
+
+    fn control_points(&self, u: (f64, f64)) -> [Point3; 4] {
+        // the control points of the part of the curve from u.0 to u.1, by blossoming
+        let lerp = |t: f64, a: Point3, b: Point3| (1.0 - t) * a + t * b;
+        let blossom = |a: f64, b: f64, c: f64| {
+            let p = self.points;
+            let (a0, a1, a2) = (lerp(a, p[0], p[1]), lerp(a, p[1], p[2]), lerp(a, p[2], p[3]));
+            lerp(c, lerp(b, a0, a1), lerp(b, a1, a2))
+        };
+        [blossom(u.0, u.0, u.0), blossom(u.0, u.0, u.1), blossom(u.0, u.1, u.1), blossom(u.1, u.1, u.1)]
+    }
+
+    fn bounding_box(&self, u: (f64, f64)) -> Aabb {
+        // the part of the curve stays inside the hull of its control points
+        let radius = 0.5 * self.width(u.0).max(self.width(u.1));
+        let rvec = Vec3::new(radius, radius, radius);
+        let points = self.control_points(u);
+        points.iter().fold(aabb::EMPTY, |bbox, &p| Aabb::surrounding(&bbox, &Aabb::from_points(p - rvec, p + rvec)))
+    }
+}
+
+struct CurveData {
+    curves: Vec<Curve>,
+    kind: CurveType,
+    mat: Box<dyn Material>,
+}
+
+struct CurveSegment {
+    data: Rc<CurveData>,
+    index: usize,
+    u: (f64, f64),
+    bbox: Aabb,
+}
+
+impl CurveSegment {
+    fn recursive_intersect(&self, cp: [Vec3; 4], z: Interval, u: (f64, f64), depth: i32) -> Option<(f64, f64)> {
+        // in ray space the ray runs up the z axis from the origin, so the curve is hit where it
+        // passes within half its width of the axis. returns the distance along the ray and u
+        let curve = &self.data.curves[self.index];
+        let radius = 0.5 * curve.width(u.0).max(curve.width(u.1));
+        let range = |axis: usize| {
+            let values = cp.map(|p| p[axis]);
+            Interval::new(values.iter().cloned().fold(f64::INFINITY, f64::min), values.iter().cloned().fold(f64::NEG_INFINITY, f64::max))
+                .expand(2.0 * radius)
+        };
+        let (x, y, zs) = (range(0), range(1), range(2));
+        if !x.contains(0.0) || !y.contains(0.0) || zs.max < z.min || zs.min > z.max {
+            return None;
+        }
+
+        if depth > 0 {
+            // the nearer hit of the two halves
+            let (first, second) = split_bezier(cp);
+            let middle = 0.5 * (u.0 + u.1);
+            let hit_first = self.recursive_intersect(first, z, (u.0, middle), depth - 1);
+            let z = hit_first.map_or(z, |(hit_z, _)| Interval::new(z.min, hit_z));
+            return self.recursive_intersect(second, z, (middle, u.1), depth - 1).or(hit_first);
+        }
+
+        // the segment is nearly straight by now. skip it if the ray passes beyond either end,
+        // where it's up to the neighbouring segment
+        let edge = (cp[1].y() - cp[0].y()) * -cp[0].y() + cp[0].x() * (cp[0].x() - cp[1].x());
+        if edge < 0.0 {
+            return None;
+        }
+        let edge = (cp[2].y() - cp[3].y()) * -cp[3].y() + cp[3].x() * (cp[3].x() - cp[2].x());
+        if edge < 0.0 {
+            return None;
+        }
+
+        // the closest point to the axis on the line through the segment's ends
+        let segment = Vec3::new(cp[3].x() - cp[0].x(), cp[3].y() - cp[0].y(), 0.0);
+        let length_squared = segment.length_squared();
+        if length_squared == 0.0 {
+            return None;
+        }
+        let w = (dot(&Vec3::new(-cp[0].x(), -cp[0].y(), 0.0), &segment) / length_squared).clamp(0.0, 1.0);
+        let hit_u = u.0 + w * (u.1 - u.0);
+
+        let pc = eval_bezier(&cp, w);
+        let half_width = 0.5 * curve.width(hit_u);
+        if pc.x() * pc.x() + pc.y() * pc.y() > half_width * half_width || !z.surrounds(pc.z()) {
+            return None;
+        }
+
+        // a ray starting inside the curve is leaving it, after scattering off it
+        if pc.length() < 1.01 * half_width {
+            return None;
+        }
+
+        Some((pc.z(), hit_u))
+    }
+}
+
+impl Hittable for CurveSegment {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let curve = &self.data.curves[self.index];
+        let length = r.direction().length();
+        let frame = Onb::new(r.direction());
+        let cp = curve.control_points(self.u).map(|p| frame.to_local(p - r.origin()));
+
+        // split finely enough that the pieces are within a twentieth of the width of straight,
+        // going by how sharply the curve bends
+        let bend = (0..2)
+            .map(|i| {
+                let d = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+                d.x().abs().max(d.y().abs()).max(d.z().abs())
+            })
+            .fold(0.0, f64::max);
+        let epsilon = 0.05 * curve.widths.0.max(curve.widths.1);
+        let depth = if bend > 0.0 { ((SQRT_2 * 6.0 * bend / (8.0 * epsilon)).log2() / 2.0).ceil() as i32 } else { 0 };
+
+        let z = Interval::new(ray_t.min * length, ray_t.max * length);
+        let (hit_z, u) = self.recursive_intersect(cp, z, self.u, depth.clamp(0, MAX_CURVE_DEPTH))?;
+        let t = hit_z / length;
+
+        // the ribbon lies across the ray, so v runs from one edge to the other by how far the hit
+        // is from the middle
+        let mut dpdu = bezier_derivative(&curve.points, u);
+        if dpdu.length_squared() == 0.0 {
+            dpdu = curve.points[3] - curve.points[0];
+        }
+        let width = curve.width(u);
+        let side = unit_vector(cross(dpdu, r.direction()));
+        let p = r.at(t);
+        let offset = (dot(&(p - eval_bezier(&curve.points, u)), &side) / (0.5 * width)).clamp(-1.0, 1.0);
+
+        let mut facing = unit_vector(cross(side, dpdu));
+        if dot(&facing, &r.direction()) > 0.0 {
+            facing = -facing;
+        }
+
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = p;
+        rec.set_face_normal(r, &facing);
+        if let CurveType::Cylinder = self.data.kind {
+            // the normal of a tube seen from the side, turning away towards its edges
+            rec.set_shading_normal(&((1.0 - offset * offset).sqrt() * facing + offset * side));
+        }
+        (rec.u, rec.v) = (u, 0.5 + 0.5 * offset);
+        (rec.dpdu, rec.dpdv) = (dpdu, width * side);
+        rec.mat = Some(&(*self.data.mat));
+
+        if !rec.is_opaque(r) {
+            return None;
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// many curves sharing a material, such as the hairs of a fur coat or the blades in a patch of
+// grass, in a bvh of their own. the curves are hit with the recursive subdivision of Nakamaru and
+// Ohno, as in pbrt, and u runs along each curve
+pub struct Curves {
+    bvh: BvhNode,
+}
+
+impl Curves {
+    pub fn new(curves: Vec<Curve>, kind: CurveType, mat: impl Material + 'static) -> Self {
+        let count = curves.len();
+        let data = Rc::new(CurveData { curves, kind, mat: Box::new(mat) });
+
+        let mut objects: Vec<Box<dyn Hittable>> = Vec::with_capacity(count * CURVE_SEGMENTS);
+        for index in 0..count {
+            for k in 0..CURVE_SEGMENTS {
+                let u = (k as f64 / CURVE_SEGMENTS as f64, (k + 1) as f64 / CURVE_SEGMENTS as f64);
+                let bbox = data.curves[index].bounding_box(u);
+                objects.push(Box::new(CurveSegment { data: Rc::clone(&data), index, u, bbox }));
+            }
+        }
+
+        Self { bvh: BvhNode::from_objects(objects) }
+    }
+}
+
+impl Hittable for Curves {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+fn eval_bezier(cp: &[Vec3; 4], u: f64) -> Vec3 {
+    // de casteljau's algorithm
+    let lerp = |a: Vec3, b: Vec3| (1.0 - u) * a + u * b;
+    let (a, b, c) = (lerp(cp[0], cp[1]), lerp(cp[1], cp[2]), lerp(cp[2], cp[3]));
+    lerp(lerp(a, b), lerp(b, c))
+}
+
+fn bezier_derivative(cp: &[Vec3; 4], u: f64) -> Vec3 {
+    3.0 * ((1.0 - u) * (1.0 - u) * (cp[1] - cp[0]) + 2.0 * u * (1.0 - u) * (cp[2] - cp[1]) + u * u * (cp[3] - cp[2]))
+}
+
+fn split_bezier(cp: [Vec3; 4]) -> ([Vec3; 4], [Vec3; 4]) {
+    // the control points of each half of the curve
+    let (a, b, c) = (0.5 * (cp[0] + cp[1]), 0.5 * (cp[1] + cp[2]), 0.5 * (cp[2] + cp[3]));
+    let (d, e) = (0.5 * (a + b), 0.5 * (b + c));
+    let middle = 0.5 * (d + e);
+    ([cp[0], a, d, middle], [middle, e, c, cp[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::INFINITY;
+    use crate::material::Lambertian;
+    use crate::vector::Color;
+
+    fn straight(widths: (f64, f64)) -> Curves {
+        // along x from -1 to 1
+        let points = [-1.0, -1.0 / 3.0, 1.0 / 3.0, 1.0].map(|x| Point3::new(x, 0.0, 0.0));
+        Curves::new(vec![Curve::new(points, widths)], CurveType::Flat, Lambertian::new(Color::new(1.0, 1.0, 1.0)))
+    }
+
+    fn hit_from_above(curves: &Curves, x: f64, y: f64) -> Option<HitRecord<'_>> {
+        curves.hit(&Ray::new(Point3::new(x, y, 5.0), Vec3::new(0.0, 0.0, -2.0)), Interval::new(0.001, INFINITY))
+    }
+
+    #[test]
+    fn straight_curve_is_hit_across_its_width() {
+        let curves = straight((0.2, 0.2));
+        for (x, y) in [(0.0, 0.0), (-0.7, 0.05), (0.4, -0.09), (0.95, 0.02)] {
+            let rec = hit_from_above(&curves, x, y).expect("missed the curve");
+            assert!((rec.t - 2.5).abs() < 1e-9, "{}", rec.t);
+            assert!((rec.u - 0.5 * (x + 1.0)).abs() < 1e-9, "{} at x = {}", rec.u, x);
+            assert!((rec.v - (0.5 + 0.5 * y / 0.1)).abs() < 1e-9, "{} at y = {}", rec.v, y);
+            assert!(rec.front_face && rec.normal.z() > 0.999);
+        }
+
+        // beside it and beyond its ends
+        assert!(hit_from_above(&curves, 0.0, 0.11).is_none());
+        assert!(hit_from_above(&curves, 0.3, -0.12).is_none());
+        assert!(hit_from_above(&curves, 1.05, 0.0).is_none());
+        assert!(hit_from_above(&curves, -1.05, 0.0).is_none());
+    }
+
+    #[test]
+    fn tapered_curve_narrows() {
+        // at x = 0.5 the width is down to 0.05
+        let curves = straight((0.2, 0.0));
+        assert!(hit_from_above(&curves, 0.5, 0.02).is_some());
+        assert!(hit_from_above(&curves, 0.5, 0.03).is_none());
+        assert!(hit_from_above(&curves, -0.5, 0.07).is_some());
+    }
+
+    #[test]
+    fn slanted_ray_turns_the_ribbon_to_face_it() {
+        let curves = straight((0.2, 0.2));
+        let r = Ray::new(Point3::new(0.3, -5.0, 5.0), Vec3::new(0.0, 5.05, -5.0));
+        let rec = curves.hit(&r, Interval::new(0.001, INFINITY)).expect("missed the curve");
+
+        // the hit is within half the width of the curve's axis, on a ribbon facing the ray
+        assert!(rec.p.y() * rec.p.y() + rec.p.z() * rec.p.z() <= 0.1 * 0.1 + 1e-9);
+        assert!((rec.p.x() - 0.3).abs() < 1e-9);
+        assert!((dot(&rec.normal, &unit_vector(r.direction())) + 1.0).abs() < 1e-9);
+    }
+}
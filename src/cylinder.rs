@@ -0,0 +1,127 @@
+use crate::aabb::Aabb;
+use crate::common::{solve_quadratic, INFINITY, PI};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{Point3, Vec3};
+
+#[derive(Copy, Clone)]
+enum Part {
+    Side,
+    Bottom,
+    Top,
+}
+
+// cylinder from the center of its base to the center of its top, optionally closed off with flat
+// caps. on the side u runs around the axis and v from base to top; the caps are mapped like disks
+pub struct Cylinder {
+    base: Point3,
+    height: f64,
+    radius: f64,
+    capped: bool,
+    frame: Onb,
+    mat: Box<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3, top: Point3, radius: f64, capped: bool, mat: impl Material + 'static) -> Self {
+        // everything is worked out in a frame where the axis runs up z from the base
+        let axis = top - base;
+        Self { base, height: axis.length(), radius, capped, frame: Onb::new(axis), mat: Box::new(mat) }
+    }
+
+    fn surface(&self, p: Point3, part: Part) -> (Vec3, (f64, f64), (Vec3, Vec3)) {
+        // outward normal, texture coordinates and tangents at a local point on the given part
+        let phi = p.y().atan2(p.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+        let dpdu = 2.0 * PI * Vec3::new(-p.y(), p.x(), 0.0);
+
+        match part {
+            Part::Side => {
+                let normal = Vec3::new(p.x(), p.y(), 0.0) / self.radius;
+                (normal, (phi / (2.0 * PI), p.z() / self.height), (dpdu, Vec3::new(0.0, 0.0, self.height)))
+            }
+            Part::Bottom | Part::Top => {
+                let normal = if let Part::Top = part { Vec3::new(0.0, 0.0, 1.0) } else { Vec3::new(0.0, 0.0, -1.0) };
+                let distance = (p.x() * p.x() + p.y() * p.y()).sqrt();
+
+                // at the center the angle is degenerate; any tangent frame will do
+                let tangents = if distance < 1e-9 {
+                    (Vec3::new(0.0, 1.0, 0.0), Vec3::new(self.radius, 0.0, 0.0))
+                } else {
+                    (dpdu, self.radius / distance * Vec3::new(p.x(), p.y(), 0.0))
+                };
+                (normal, (phi / (2.0 * PI), distance / self.radius), tangents)
+            }
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let o = self.frame.to_local(r.origin() - self.base);
+        let d = self.frame.to_local(r.direction());
+
+        let mut candidates = [(INFINITY, Part::Side); 4];
+        let mut count = 0;
+
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (o.x() * d.x() + o.y() * d.y());
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for t in [t0, t1] {
+                let z = o.z() + t * d.z();
+                if (0.0..=self.height).contains(&z) {
+                    candidates[count] = (t, Part::Side);
+                    count += 1;
+                }
+            }
+        }
+
+        if self.capped && d.z().abs() > 1e-12 {
+            for (z, part) in [(0.0, Part::Bottom), (self.height, Part::Top)] {
+                let t = (z - o.z()) / d.z();
+                let (x, y) = (o.x() + t * d.x(), o.y() + t * d.y());
+                if x * x + y * y <= self.radius * self.radius {
+                    candidates[count] = (t, part);
+                    count += 1;
+                }
+            }
+        }
+
+        // the nearest hit that a cutout material doesn't let the ray through
+        candidates[..count].sort_by(|a, b| a.0.total_cmp(&b.0));
+        for &(t, part) in &candidates[..count] {
+            if !ray_t.surrounds(t) {
+                continue;
+            }
+
+            let (normal, uv, (dpdu, dpdv)) = self.surface(o + t * d, part);
+
+            let mut rec = HitRecord::new();
+            rec.t = t;
+            rec.p = r.at(t);
+            rec.set_face_normal(r, &self.frame.to_world(normal));
+            (rec.u, rec.v) = uv;
+            (rec.dpdu, rec.dpdv) = (self.frame.to_world(dpdu), self.frame.to_world(dpdv));
+            rec.mat = Some(&(*self.mat));
+
+            if rec.is_opaque(r) {
+                return Some(rec);
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let axis = self.frame.to_world(Vec3::new(0.0, 0.0, 1.0));
+        let top = self.base + self.height * axis;
+        Aabb::surrounding(
+            &Aabb::around_disk(self.base, axis, self.radius),
+            &Aabb::around_disk(top, axis, self.radius),
+        )
+    }
+}
@@ -0,0 +1,75 @@
+use crate::aabb::Aabb;
+use crate::common::PI;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{dot, unit_vector, Point3, Vec3};
+
+// flat circle facing along `normal`. u is the angle around the center and v the distance from it,
+// both from 0 to 1
+pub struct Disk {
+    center: Point3,
+    normal: Vec3,
+    radius: f64,
+    frame: Onb,
+    mat: Box<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, mat: impl Material + 'static) -> Self {
+        let normal = unit_vector(normal);
+        Self { center, normal, radius, frame: Onb::new(normal), mat: Box::new(mat) }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = dot(&self.normal, &r.direction());
+        if denom.abs() < 1e-8 {
+            // the ray runs parallel to the disk
+            return None;
+        }
+
+        let t = dot(&self.normal, &(self.center - r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let p = r.at(t);
+        let local = self.frame.to_local(p - self.center);
+        let distance = (local.x() * local.x() + local.y() * local.y()).sqrt();
+        if distance > self.radius {
+            return None;
+        }
+
+        let phi = local.y().atan2(local.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = p;
+        rec.set_face_normal(r, &self.normal);
+        (rec.u, rec.v) = (phi / (2.0 * PI), distance / self.radius);
+
+        // at the center the angle is degenerate; any tangent frame will do
+        let (dpdu, dpdv) = if distance < 1e-9 {
+            (Vec3::new(0.0, 1.0, 0.0), Vec3::new(self.radius, 0.0, 0.0))
+        } else {
+            (2.0 * PI * Vec3::new(-local.y(), local.x(), 0.0), self.radius / distance * Vec3::new(local.x(), local.y(), 0.0))
+        };
+        (rec.dpdu, rec.dpdv) = (self.frame.to_world(dpdu), self.frame.to_world(dpdv));
+        rec.mat = Some(&(*self.mat));
+
+        if !rec.is_opaque(r) {
+            return None;
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::around_disk(self.center, self.normal, self.radius)
+    }
+}
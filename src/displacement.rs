@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::mesh::{edge_key, Mesh, TriangleMesh};
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vector::{cross, unit_vector, Vec3};
+
+// most rounds of splitting the mesh's edges in half, which shrinks them by up to 2^16
+const MAX_DICING_ROUNDS: i32 = 16;
+
+// most triangles dicing may make, as each round can quadruple them
+const MAX_DICED_TRIANGLES: f64 = 16_000_000.0;
+
+// a mesh with each point pushed out along its normal by `height`, read from the texture's first
+// channel and multiplied by `scale` in world units. the mesh is diced into triangles no longer than
+// `edge_length` and displaced once, when it's built, so the bvh over the result and every box in
+// it already take in how far the surface moved
+pub struct DisplacedMesh {
+    mesh: TriangleMesh,
+}
+
+impl DisplacedMesh {
+    pub fn new(
+        mesh: &Mesh,
+        height: Arc<dyn Texture>,
+        scale: f64,
+        edge_length: f64,
+        mat: impl Material + 'static,
+    ) -> io::Result<Self> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+        if edge_length.is_nan() || edge_length <= 0.0 {
+            return Err(invalid(format!("Could not dice a mesh to an edge length of {edge_length}.")));
+        }
+        let most = most_diced_triangles(mesh, edge_length);
+        if most > MAX_DICED_TRIANGLES {
+            return Err(invalid(format!(
+                "Dicing the mesh to an edge length of {edge_length} could make {most:.0} triangles, more than {MAX_DICED_TRIANGLES}."
+            )));
+        }
+
+        let (mut diced, normals) = dice(mesh, edge_length);
+        for (i, p) in diced.positions.iter_mut().enumerate() {
+            let (u, v) = diced.uvs.get(i).copied().unwrap_or((0.0, 0.0));
+            *p = *p + scale * height.value(u, v, p).x() * unit_vector(normals[i]);
+        }
+
+        Ok(Self { mesh: TriangleMesh::new(&diced, mat) })
+    }
+}
+
+impl Hittable for DisplacedMesh {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.mesh.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.mesh.bounding_box()
+    }
+}
+
+fn most_diced_triangles(mesh: &Mesh, edge_length: f64) -> f64 {
+    // how many triangles dicing could make at worst: every round a triangle's edges are still too
+    // long, it may split into four
+    let length = |a: usize, b: usize| (mesh.positions[a] - mesh.positions[b]).length();
+    mesh.faces
+        .iter()
+        .flat_map(|face| (1..face.len() - 1).map(move |k| [face[0], face[k], face[k + 1]]))
+        .map(|[a, b, c]| {
+            let longest = length(a, b).max(length(b, c)).max(length(c, a));
+            let rounds = (longest / edge_length).log2().ceil().clamp(0.0, MAX_DICING_ROUNDS as f64);
+            4f64.powf(rounds)
+        })
+        .sum()
+}
+
+fn dice(mesh: &Mesh, edge_length: f64) -> (Mesh, Vec<Vec3>) {
+    // splits every edge longer than `edge_length` at its midpoint, and each triangle into the
+    // pieces its split edges call for, until none are left too long. whether an edge splits
+    // depends only on the edge, so neighbouring triangles always agree and the diced mesh has no
+    // cracks for the displacement to pull open. returns the triangles, along with a normal for
+    // every vertex averaged from the faces around it
+    let mut triangles: Vec<[usize; 3]> = mesh
+        .faces
+        .iter()
+        .flat_map(|face| (1..face.len() - 1).map(move |k| [face[0], face[k], face[k + 1]]))
+        .collect();
+    let (mut positions, mut uvs) = (mesh.positions.clone(), mesh.uvs.clone());
+
+    let mut normals = vec![Vec3::new(0.0, 0.0, 0.0); positions.len()];
+    for &[a, b, c] in &triangles {
+        let normal = cross(positions[b] - positions[a], positions[c] - positions[a]);
+        for i in [a, b, c] {
+            normals[i] = normals[i] + normal;
+        }
+    }
+
+    for _ in 0..MAX_DICING_ROUNDS {
+        let mut midpoints = HashMap::new();
+        for triangle in &triangles {
+            for k in 0..3 {
+                let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                if (positions[a] - positions[b]).length() > edge_length {
+                    midpoints.entry(edge_key(a, b)).or_insert_with(|| {
+                        positions.push(0.5 * (positions[a] + positions[b]));
+                        normals.push(unit_vector(normals[a]) + unit_vector(normals[b]));
+                        if !uvs.is_empty() {
+                            uvs.push((0.5 * (uvs[a].0 + uvs[b].0), 0.5 * (uvs[a].1 + uvs[b].1)));
+                        }
+                        positions.len() - 1
+                    });
+                }
+            }
+        }
+
+        if midpoints.is_empty() {
+            break;
+        }
+
+        let mut split = Vec::with_capacity(4 * triangles.len());
+        for triangle in triangles {
+            let midpoint = |k: usize| midpoints.get(&edge_key(triangle[k], triangle[(k + 1) % 3])).copied();
+
+            // turn the triangle so that a, b is split if any edge is and c, a isn't if any edge
+            // isn't, which leaves one way to cut it up for each number of split edges
+            let turn = (0..3)
+                .find(|&k| midpoint(k).is_some() && midpoint((k + 2) % 3).is_none())
+                .unwrap_or(0);
+            let [a, b, c] = [triangle[turn], triangle[(turn + 1) % 3], triangle[(turn + 2) % 3]];
+
+            match [midpoint(turn), midpoint((turn + 1) % 3), midpoint((turn + 2) % 3)] {
+                [Some(ab), Some(bc), Some(ca)] => split.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]),
+                [Some(ab), Some(bc), None] => split.extend([[a, ab, c], [ab, b, bc], [ab, bc, c]]),
+                [Some(ab), None, None] => split.extend([[a, ab, c], [ab, b, c]]),
+                _ => split.push([a, b, c]),
+            }
+        }
+        triangles = split;
+    }
+
+    let faces = triangles.iter().map(|triangle| triangle.to_vec()).collect();
+    (Mesh { positions, uvs, faces, creases: HashMap::new() }, normals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::texture::SolidColor;
+    use crate::vector::{Color, Point3};
+
+    fn square() -> Mesh {
+        Mesh::new(
+            vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(1.0, 0.0, 1.0), Point3::new(0.0, 0.0, 1.0)],
+            vec![vec![0, 3, 2, 1]],
+        )
+    }
+
+    #[test]
+    fn dicing_leaves_no_edge_too_long() {
+        let (diced, normals) = dice(&square(), 0.1);
+        assert_eq!(normals.len(), diced.positions.len());
+        for face in &diced.faces {
+            for k in 0..3 {
+                let (a, b) = (diced.positions[face[k]], diced.positions[face[(k + 1) % 3]]);
+                assert!((a - b).length() <= 0.1);
+            }
+        }
+    }
+
+    #[test]
+    fn dicing_too_finely_is_refused_up_front() {
+        let flat = || Lambertian::new(Color::new(0.5, 0.5, 0.5));
+        let height = || -> Arc<dyn Texture> { Arc::new(SolidColor::gray(0.0)) };
+
+        let error = DisplacedMesh::new(&square(), height(), 1.0, 1e-6, flat()).err().expect("diced to a millionth");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(DisplacedMesh::new(&square(), height(), 1.0, 0.0, flat()).is_err());
+        assert!(DisplacedMesh::new(&square(), height(), 1.0, 0.05, flat()).is_ok());
+    }
+}
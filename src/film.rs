@@ -0,0 +1,155 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+
+use crate::checkpoint::{read_f64, read_i32, write_f64, write_i32};
+
+use crate::filter::Filter;
+use crate::vector::{luminance, Color};
+
+#[derive(Copy, Clone)]
+struct FilmPixel {
+    color_sum: Color,
+    weight_sum: f64,
+}
+
+// the image being rendered. every sample is splatted into all the pixels its filter covers, and
+// a pixel's final color is the filter-weighted average of the samples around it
+pub struct Film {
+    width: i32,
+    height: i32,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(width: i32, height: i32) -> Self {
+        let pixel = FilmPixel { color_sum: Color::new(0.0, 0.0, 0.0), weight_sum: 0.0 };
+        Self { width, height, pixels: vec![pixel; (width * height) as usize] }
+    }
+
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color, filter: &dyn Filter) {
+        // x, y is the sample position in pixel units, with pixel i, j centered on i, j
+        let radius = filter.radius();
+        let x0 = ((x - radius).ceil() as i32).max(0);
+        let x1 = ((x + radius).floor() as i32).min(self.width - 1);
+        let y0 = ((y - radius).ceil() as i32).max(0);
+        let y1 = ((y + radius).floor() as i32).min(self.height - 1);
+
+        for j in y0..=y1 {
+            for i in x0..=x1 {
+                let weight = filter.evaluate(i as f64 - x, j as f64 - y);
+                if weight == 0.0 {
+                    continue;
+                }
+
+                let pixel = &mut self.pixels[(j * self.width + i) as usize];
+                pixel.color_sum = pixel.color_sum + weight * color;
+                pixel.weight_sum += weight;
+            }
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn save(&self, w: &mut impl Write) -> io::Result<()> {
+        // raw accumulated sums, bit for bit, so a reloaded film continues exactly
+        write_i32(w, self.width)?;
+        write_i32(w, self.height)?;
+        for pixel in self.pixels.iter() {
+            write_f64(w, pixel.color_sum.x())?;
+            write_f64(w, pixel.color_sum.y())?;
+            write_f64(w, pixel.color_sum.z())?;
+            write_f64(w, pixel.weight_sum)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn load(r: &mut impl Read) -> io::Result<Self> {
+        let width = read_i32(r)?;
+        let height = read_i32(r)?;
+        let mut film = Film::new(width, height);
+        for pixel in film.pixels.iter_mut() {
+            pixel.color_sum = Color::new(read_f64(r)?, read_f64(r)?, read_f64(r)?);
+            pixel.weight_sum = read_f64(r)?;
+        }
+
+        Ok(film)
+    }
+
+    pub fn pixel_color(&self, i: i32, j: i32) -> Color {
+        let pixel = &self.pixels[(j * self.width + i) as usize];
+
+        // filters with negative lobes can leave a pixel with no usable weight
+        if pixel.weight_sum <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        pixel.color_sum / pixel.weight_sum
+    }
+
+    pub fn write_ppm(&self, filename: &str) {
+        // write to a temporary file and move it into place, so an interrupted render never leaves
+        // a half-written image behind
+        let temp_filename = format!("{}.tmp", filename);
+        let mut file = BufWriter::new(File::create(&temp_filename).expect("Could not create file."));
+
+        let header = std::format!("P6\n{} {}\n255\n", self.width, self.height);
+        file.write_all(header.as_bytes())
+            .expect("Could not write to file.");
+
+        for j in 0..self.height {
+            for i in 0..self.width {
+                self.pixel_color(i, j).write(&mut file);
+            }
+        }
+
+        file.flush().expect("Could not write to file.");
+        drop(file);
+        fs::rename(&temp_filename, filename).expect("Could not write to file.");
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct PixelStats {
+    // running sums of the luminance of a pixel's own samples, used for the error estimate
+    pub luminance_sum: f64,
+    pub luminance_sq_sum: f64,
+    pub count: i32,
+    pub done: bool, // converged or out of samples
+}
+
+impl PixelStats {
+    pub fn new() -> Self {
+        Self {
+            luminance_sum: 0.0,
+            luminance_sq_sum: 0.0,
+            count: 0,
+            done: false,
+        }
+    }
+
+    pub fn add(&mut self, sample: Color) {
+        let y = luminance(sample);
+        self.luminance_sum += y;
+        self.luminance_sq_sum += y * y;
+        self.count += 1;
+    }
+
+    pub fn error(&self) -> f64 {
+        // standard error of the mean luminance, measured after gamma correction so that dark and
+        // bright pixels are held to the same visible tolerance
+        let n = self.count as f64;
+        let mean = self.luminance_sum / n;
+        let variance = ((self.luminance_sq_sum - n * mean * mean) / (n - 1.0)).max(0.0);
+        let std_error = (variance / n).sqrt();
+
+        // d(sqrt(x))/dx = 1 / (2 sqrt(x))
+        std_error / (2.0 * mean.max(1e-4).sqrt())
+    }
+}
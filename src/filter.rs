@@ -0,0 +1,201 @@
+use std::str::FromStr;
+
+use crate::common::PI;
+
+pub trait Filter {
+    // half-width of the filter's support, in pixels
+    fn radius(&self) -> f64;
+    // weight of a sample at offset x, y from a pixel center
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FilterType {
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+}
+
+impl FilterType {
+    pub fn default_radius(&self) -> f64 {
+        match self {
+            FilterType::Box => 0.5,
+            FilterType::Tent => 1.0,
+            FilterType::Gaussian => 1.5,
+            FilterType::Mitchell => 2.0,
+            FilterType::Lanczos => 3.0,
+        }
+    }
+
+    pub fn create(&self, radius: f64) -> Box<dyn Filter> {
+        match self {
+            FilterType::Box => Box::new(BoxFilter::new(radius)),
+            FilterType::Tent => Box::new(TentFilter::new(radius)),
+            FilterType::Gaussian => Box::new(GaussianFilter::new(radius, radius / 3.0)),
+            FilterType::Mitchell => Box::new(MitchellFilter::new(radius, 1.0 / 3.0, 1.0 / 3.0)),
+            FilterType::Lanczos => Box::new(LanczosFilter::new(radius)),
+        }
+    }
+}
+
+impl FromStr for FilterType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "box" => Ok(FilterType::Box),
+            "tent" | "triangle" => Ok(FilterType::Tent),
+            "gaussian" => Ok(FilterType::Gaussian),
+            "mitchell" => Ok(FilterType::Mitchell),
+            "lanczos" => Ok(FilterType::Lanczos),
+            _ => Err(format!("unknown filter '{}'", s)),
+        }
+    }
+}
+
+pub struct BoxFilter {
+    radius: f64,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        if x.abs() <= self.radius && y.abs() <= self.radius { 1.0 } else { 0.0 }
+    }
+}
+
+pub struct TentFilter {
+    radius: f64,
+}
+
+impl TentFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+pub struct GaussianFilter {
+    radius: f64,
+    sigma: f64,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f64, sigma: f64) -> Self {
+        Self { radius, sigma }
+    }
+
+    fn gaussian(&self, x: f64) -> f64 {
+        // shifted down so the filter falls to exactly zero at its radius
+        let g = |x: f64| (-x * x / (2.0 * self.sigma * self.sigma)).exp();
+        (g(x) - g(self.radius)).max(0.0)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+// Mitchell-Netravali cubic. b = c = 1/3 is the authors' recommended compromise between blurring
+// and ringing
+pub struct MitchellFilter {
+    radius: f64,
+    b: f64,
+    c: f64,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f64, b: f64, c: f64) -> Self {
+        Self { radius, b, c }
+    }
+
+    fn mitchell(&self, x: f64) -> f64 {
+        // x is in [-1, 1] over the filter's support; the cubic itself is defined over [-2, 2]
+        let x = (2.0 * x).abs();
+        let (b, c) = (self.b, self.c);
+
+        if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            ((-b - 6.0 * c) * x.powi(3) + (6.0 * b + 30.0 * c) * x.powi(2) + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3) + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2) + (6.0 - 2.0 * b))
+                / 6.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.mitchell(x / self.radius) * self.mitchell(y / self.radius)
+    }
+}
+
+// sinc windowed by a wider sinc, with as many lobes as the radius
+pub struct LanczosFilter {
+    radius: f64,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+
+    fn lanczos(&self, x: f64) -> f64 {
+        if x.abs() > self.radius {
+            return 0.0;
+        }
+
+        sinc(x) * sinc(x / self.radius)
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.lanczos(x) * self.lanczos(y)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.0;
+    }
+
+    (PI * x).sin() / (PI * x)
+}
@@ -0,0 +1,284 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::aabb::Aabb;
+use crate::common::random_f64;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{Color, Point3, Vec3};
+
+// a dense grid of voxels, each holding `channels` values, such as density and temperature from a
+// smoke simulation. the file format is a text header followed by raw little-endian f32 values,
+// channels interleaved, with x varying fastest and z slowest:
+//
+//     RTGRID
+//     <nx> <ny> <nz> <channels>
+//     <nx * ny * nz * channels floats>
+pub struct VoxelGrid {
+    nx: usize,
+    ny: usize,
+    nz: usize,
+    channels: usize,
+    data: Vec<f32>,
+}
+
+impl VoxelGrid {
+    pub fn new(nx: usize, ny: usize, nz: usize, channels: usize, data: Vec<f32>) -> Self {
+        assert_eq!(data.len(), nx * ny * nz * channels, "Could not fit the voxel data to the grid size.");
+        Self { nx, ny, nz, channels, data }
+    }
+
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut r = BufReader::new(File::open(filename)?);
+
+        let mut line = String::new();
+        r.read_line(&mut line)?;
+        if line.trim() != "RTGRID" {
+            return Err(invalid("not a voxel grid"));
+        }
+
+        line.clear();
+        r.read_line(&mut line)?;
+        let sizes: Vec<usize> = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("bad voxel grid size"))?;
+        if sizes.len() != 4 || sizes.contains(&0) {
+            return Err(invalid("bad voxel grid size"));
+        }
+
+        let (nx, ny, nz, channels) = (sizes[0], sizes[1], sizes[2], sizes[3]);
+        let mut bytes = vec![0; nx * ny * nz * channels * 4];
+        r.read_exact(&mut bytes)?;
+        let data = bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect();
+
+        Ok(Self::new(nx, ny, nz, channels, data))
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    pub fn max(&self, channel: usize) -> f64 {
+        self.data.iter().skip(channel).step_by(self.channels).fold(0.0f32, |a, &b| a.max(b)) as f64
+    }
+
+    pub fn lookup(&self, p: Vec3, channel: usize) -> f64 {
+        // trilinear interpolation between voxel centers, where p runs from 0 to 1 across the grid
+        // on each axis. values are held constant past the outermost centers
+        let x = p.x() * self.nx as f64 - 0.5;
+        let y = p.y() * self.ny as f64 - 0.5;
+        let z = p.z() * self.nz as f64 - 0.5;
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (tx, ty, tz) = (x - x0, y - y0, z - z0);
+
+        let voxel = |i: f64, j: f64, k: f64| {
+            let i = (i.max(0.0) as usize).min(self.nx - 1);
+            let j = (j.max(0.0) as usize).min(self.ny - 1);
+            let k = (k.max(0.0) as usize).min(self.nz - 1);
+            self.data[((k * self.ny + j) * self.nx + i) * self.channels + channel] as f64
+        };
+        let lerp = |a: f64, b: f64, t: f64| (1.0 - t) * a + t * b;
+
+        let c00 = lerp(voxel(x0, y0, z0), voxel(x0 + 1.0, y0, z0), tx);
+        let c10 = lerp(voxel(x0, y0 + 1.0, z0), voxel(x0 + 1.0, y0 + 1.0, z0), tx);
+        let c01 = lerp(voxel(x0, y0, z0 + 1.0), voxel(x0 + 1.0, y0, z0 + 1.0), tx);
+        let c11 = lerp(voxel(x0, y0 + 1.0, z0 + 1.0), voxel(x0 + 1.0, y0 + 1.0, z0 + 1.0), tx);
+
+        lerp(lerp(c00, c10, ty), lerp(c01, c11, ty), tz)
+    }
+}
+
+// smoke or fire whose density varies through a voxel grid stretched over a box. the first channel
+// is density, multiplied by `density_scale`; a second channel, if there is one, is how strongly
+// each point glows, multiplied by `emission`. collisions are sampled with delta tracking against
+// the grid's largest density, so empty regions cost a few extra steps rather than any bias, and
+// the light getting through for shadow and visibility queries is estimated with ratio tracking.
+// `albedo` is the fraction of each collision that scatters rather than absorbs; the phase function
+// only chooses the new direction, so it should have a white albedo of its own
+pub struct GridMedium {
+    bbox: Aabb,
+    grid: VoxelGrid,
+    density_scale: f64,
+    majorant: f64,
+    albedo: Color,
+    emission: Color,
+    phase_function: Box<dyn Material>,
+}
+
+impl GridMedium {
+    pub fn new(
+        grid: VoxelGrid,
+        min: Point3,
+        max: Point3,
+        density_scale: f64,
+        albedo: Color,
+        emission: Color,
+        phase_function: impl Material + 'static,
+    ) -> Self {
+        let majorant = density_scale * grid.max(0);
+
+        Self {
+            bbox: Aabb::from_points(min, max),
+            grid,
+            density_scale,
+            majorant,
+            albedo,
+            emission,
+            phase_function: Box::new(phase_function),
+        }
+    }
+
+    fn to_grid(&self, p: Point3) -> Vec3 {
+        Vec3::new(
+            (p.x() - self.bbox.x.min) / self.bbox.x.size(),
+            (p.y() - self.bbox.y.min) / self.bbox.y.size(),
+            (p.z() - self.bbox.z.min) / self.bbox.z.size(),
+        )
+    }
+
+    fn density(&self, p: Point3) -> f64 {
+        self.density_scale * self.grid.lookup(self.to_grid(p), 0)
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let ray_t = self.bbox.clip(r, ray_t)?;
+        if self.majorant <= 0.0 {
+            return None;
+        }
+
+        // delta tracking: step to tentative collisions as if the whole box were at the largest
+        // density, and accept each with the ratio of the actual density to that
+        let step = 1.0 / (self.majorant * r.direction().length());
+        let mut t = ray_t.min;
+        loop {
+            t -= step * (1.0 - random_f64()).ln();
+            if t >= ray_t.max {
+                return None;
+            }
+
+            let p = r.at(t);
+            if random_f64() * self.majorant < self.density(p) {
+                let mut rec = HitRecord::new();
+                rec.t = t;
+                rec.p = p;
+
+                // a medium has no surface, so the normal is arbitrary
+                rec.normal = Vec3::new(1.0, 0.0, 0.0);
+                rec.geometric_normal = rec.normal;
+                rec.front_face = true;
+                rec.mat = Some(self);
+
+                return Some(rec);
+            }
+        }
+    }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        // ratio tracking: the same tentative collisions as delta tracking, but rather than
+        // stopping at one, each scales what gets through by the chance it was a null collision
+        let Some(ray_t) = self.bbox.clip(r, ray_t) else {
+            return 1.0;
+        };
+        if self.majorant <= 0.0 {
+            return 1.0;
+        }
+
+        let step = 1.0 / (self.majorant * r.direction().length());
+        let mut transmittance = 1.0;
+        let mut t = ray_t.min;
+        loop {
+            t -= step * (1.0 - random_f64()).ln();
+            if t >= ray_t.max {
+                return transmittance;
+            }
+            transmittance *= 1.0 - self.density(r.at(t)) / self.majorant;
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+// the medium is its own material, so that emission can be looked up in the grid
+impl Material for GridMedium {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let (attenuation, scattered) = self.phase_function.scatter(r_in, record, sampler)?;
+        Some((self.albedo * attenuation, scattered))
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        if self.grid.channels() < 2 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+
+        // only the absorbed part of a collision emits, sigma_a / sigma_t of it, as the rest
+        // scatters and carries light from elsewhere
+        let absorption = Color::new(1.0, 1.0, 1.0) - self.albedo;
+        self.grid.lookup(self.to_grid(*p), 1) * absorption * self.emission
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::seed_random;
+    use crate::hittable_list::HittableList;
+    use crate::material::Isotropic;
+    use crate::sphere::Sphere;
+
+    const RAYS: usize = 20000;
+
+    fn ramp() -> GridMedium {
+        // density rising along x from 0.4 to 2, held flat past the two voxel centers, so the
+        // optical depth straight across the unit box is 2 * (0.25 * 0.2 + 0.5 * 0.6 + 0.25 * 1)
+        let grid = VoxelGrid::new(2, 1, 1, 1, vec![0.2, 1.0]);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let min = Point3::new(0.0, 0.0, 0.0);
+        let max = Point3::new(1.0, 1.0, 1.0);
+        GridMedium::new(grid, min, max, 2.0, white, Color::new(0.0, 0.0, 0.0), Isotropic::new(white))
+    }
+
+    fn across() -> Ray {
+        Ray::new(Point3::new(-1.0, 0.5, 0.5), Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn ratio_tracking_matches_delta_tracking_and_optical_depth() {
+        seed_random(11);
+        let medium = ramp();
+        let expected = (-1.2f64).exp();
+
+        let ratio = (0..RAYS).map(|_| medium.transmittance(&across(), Interval::new(0.0, 10.0))).sum::<f64>();
+        let escaped = (0..RAYS).filter(|_| medium.hit(&across(), Interval::new(0.0, 10.0)).is_none()).count();
+
+        let ratio = ratio / RAYS as f64;
+        let escaped = escaped as f64 / RAYS as f64;
+        assert!((ratio - expected).abs() < 0.01, "ratio tracking gave {ratio}, expected {expected}");
+        assert!((escaped - expected).abs() < 0.02, "delta tracking let {escaped} through, expected {expected}");
+    }
+
+    #[test]
+    fn visibility_through_a_scene_combines_media_and_surfaces() {
+        seed_random(12);
+        let mut world = HittableList::new();
+        world.add(ramp());
+
+        // stopping short of the box sees nothing of the medium
+        assert_eq!(world.transmittance(&across(), Interval::new(0.0, 0.9)), 1.0);
+
+        let through = (0..RAYS).map(|_| world.transmittance(&across(), Interval::new(0.0, 10.0))).sum::<f64>();
+        assert!(through > 0.0 && through < RAYS as f64);
+
+        world.add(Sphere::new(Point3::new(3.0, 0.5, 0.5), 0.5, Isotropic::new(Color::new(1.0, 1.0, 1.0))));
+        assert_eq!(world.transmittance(&across(), Interval::new(0.0, 10.0)), 0.0);
+    }
+}
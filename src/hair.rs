@@ -0,0 +1,307 @@
+use std::f64::consts::LN_2;
+
+use crate::common::PI;
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::microfacet::fresnel_dielectric;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{cross, dot, luminance, unit_vector, Color, Vec3};
+
+// how many times light is followed through a hair before the rest is lumped into one lobe
+const P_MAX: usize = 3;
+
+// index of refraction of the keratin hair is made of
+const HAIR_ETA: f64 = 1.55;
+
+// absorption of the two pigments hair gets its color from, per unit of concentration
+const EUMELANIN_SIGMA_A: (f64, f64, f64) = (0.419, 0.697, 1.37);
+const PHEOMELANIN_SIGMA_A: (f64, f64, f64) = (0.187, 0.4, 1.05);
+
+// the hair scattering model of Marschner et al., in the energy conserving form of d'Eon et al.
+// used by pbrt. light reflects off the fiber (R), passes through it (TT) or reflects once inside
+// (TRT), with the rest gathered into a fourth lobe. `sigma_a` is absorption per unit of the
+// hair's diameter, `beta_m` and `beta_n` the longitudinal and azimuthal roughness from 0 to 1,
+// and `alpha` how many degrees the scales on the fiber's surface tilt it. meant for flat curves,
+// since the hair's roundness comes from where across the ribbon it's hit
+pub struct Hair {
+    sigma_a: Color,
+    v: [f64; P_MAX + 1],
+    s: f64,
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+}
+
+impl Hair {
+    pub fn new(sigma_a: Color, beta_m: f64, beta_n: f64, alpha: f64) -> Self {
+        // longitudinal variance of each lobe, from a fit to the roughness
+        let v0 = (0.726 * beta_m + 0.812 * beta_m * beta_m + 3.7 * beta_m.powi(20)).powi(2);
+        let v = [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0];
+
+        // azimuthal logistic scale
+        let s = (PI / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n * beta_n + 5.372 * beta_n.powi(22));
+
+        // the scale tilt, doubled for each lobe after the first
+        let mut sin_2k_alpha = [alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [(1.0 - sin_2k_alpha[0] * sin_2k_alpha[0]).max(0.0).sqrt(), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1] * cos_2k_alpha[i - 1] - sin_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+        }
+
+        Self { sigma_a, v, s, sin_2k_alpha, cos_2k_alpha }
+    }
+
+    pub fn from_melanin(eumelanin: f64, pheomelanin: f64, beta_m: f64, beta_n: f64, alpha: f64) -> Self {
+        // eumelanin runs from blond around 0.3 to black at 8, and pheomelanin adds red
+        let (e, p) = (EUMELANIN_SIGMA_A, PHEOMELANIN_SIGMA_A);
+        let sigma_a = eumelanin * Color::new(e.0, e.1, e.2) + pheomelanin * Color::new(p.0, p.1, p.2);
+        Self::new(sigma_a, beta_m, beta_n, alpha)
+    }
+
+    fn tilted(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        // the outgoing angle as seen by lobe p, turned by the scales: R by -2 alpha, TT by alpha
+        // and TRT by 4 alpha
+        let (sin, cos) = match p {
+            0 => (-self.sin_2k_alpha[1], self.cos_2k_alpha[1]),
+            1 => (self.sin_2k_alpha[0], self.cos_2k_alpha[0]),
+            2 => (self.sin_2k_alpha[2], self.cos_2k_alpha[2]),
+            _ => return (sin_theta_o, cos_theta_o),
+        };
+        (sin_theta_o * cos + cos_theta_o * sin, (cos_theta_o * cos - sin_theta_o * sin).abs())
+    }
+}
+
+impl Material for Hair {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        // x runs along the hair, y across it and z towards the incoming ray
+        let x = unit_vector(record.dpdu);
+        let z = if record.front_face { record.geometric_normal } else { -record.geometric_normal };
+        let y = unit_vector(cross(z, x));
+        let d = -unit_vector(r_in.direction());
+        let wo = Vec3::new(dot(&d, &x), dot(&d, &y), dot(&d, &z));
+
+        let sin_theta_o = wo.x().clamp(-1.0, 1.0);
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z().atan2(wo.y());
+
+        // where across the fiber the ray enters, and the angle it refracts to inside
+        let h = (-1.0 + 2.0 * record.v).clamp(-1.0, 1.0);
+        let gamma_o = h.asin();
+        let sin_theta_t = sin_theta_o / HAIR_ETA;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+        let eta_p = (HAIR_ETA * HAIR_ETA - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o.max(1e-6);
+        let sin_gamma_t = (h / eta_p).clamp(-1.0, 1.0);
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let gamma_t = sin_gamma_t.asin();
+
+        // transmittance across the fiber once, and how much of the light each lobe carries
+        let path = 2.0 * cos_gamma_t / cos_theta_t;
+        let transmittance = Color::new(
+            (-self.sigma_a.x() * path).exp(),
+            (-self.sigma_a.y() * path).exp(),
+            (-self.sigma_a.z() * path).exp(),
+        );
+        let ap = attenuations(cos_theta_o, h, transmittance);
+        let total: f64 = ap.iter().map(|&a| luminance(a)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+        let ap_pdf = ap.map(|a| luminance(a) / total);
+
+        // pick a lobe by how much light it carries, then a longitudinal and an azimuthal angle
+        // from it
+        let mut choice = sampler.get_1d();
+        let mut p = P_MAX;
+        for (i, &pdf) in ap_pdf.iter().enumerate().take(P_MAX) {
+            if choice < pdf {
+                p = i;
+                break;
+            }
+            choice -= pdf;
+        }
+
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+        let (u0, u1) = sampler.get_2d();
+        let u0 = u0.max(1e-5);
+        let cos_theta = 1.0 + self.v[p] * (u0 + (1.0 - u0) * (-2.0 / self.v[p]).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let sin_theta_i = (-cos_theta * sin_theta_op + sin_theta * (2.0 * PI * u1).cos() * cos_theta_op).clamp(-1.0, 1.0);
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        let u = sampler.get_1d();
+        let dphi = if p < P_MAX {
+            phi(p, gamma_o, gamma_t) + sample_trimmed_logistic(u, self.s)
+        } else {
+            2.0 * PI * u
+        };
+        let phi_i = phi_o + dphi;
+        let wi = Vec3::new(sin_theta_i, cos_theta_i * phi_i.cos(), cos_theta_i * phi_i.sin());
+
+        // the whole model and its pdf over every lobe for the chosen direction. the cosine the
+        // model is divided by cancels against the one in the rendering equation
+        let mut f = Color::new(0.0, 0.0, 0.0);
+        let mut pdf = 0.0;
+        for lobe in 0..=P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilted(lobe, sin_theta_o, cos_theta_o);
+            let m = mp(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, self.v[lobe]);
+            let n = if lobe < P_MAX { np(dphi, lobe, self.s, gamma_o, gamma_t) } else { 1.0 / (2.0 * PI) };
+            f = f + m * n * ap[lobe];
+            pdf += m * n * ap_pdf[lobe];
+        }
+        if pdf <= 0.0 {
+            return None;
+        }
+
+        let direction = wi.x() * x + wi.y() * y + wi.z() * z;
+        Some((f / pdf, Ray::new(record.p, direction)))
+    }
+}
+
+fn attenuations(cos_theta_o: f64, h: f64, transmittance: Color) -> [Color; P_MAX + 1] {
+    // the fraction of light in each lobe: reflected straight away, or transmitted in and then out
+    // after some number of reflections inside, with all the later ones summed as a series
+    let cos_gamma_o = safe_sqrt(1.0 - h * h);
+    let f = fresnel_dielectric(cos_theta_o * cos_gamma_o, HAIR_ETA);
+
+    let r = Color::new(f, f, f);
+    let tt = (1.0 - f) * (1.0 - f) * transmittance;
+    let trt = f * tt * transmittance;
+    let rest_scale = |t: f64| f * t / (1.0 - t * f);
+    let rest = trt * Color::new(rest_scale(transmittance.x()), rest_scale(transmittance.y()), rest_scale(transmittance.z()));
+
+    [r, tt, trt, rest]
+}
+
+fn mp(cos_theta_i: f64, cos_theta_o: f64, sin_theta_i: f64, sin_theta_o: f64, v: f64) -> f64 {
+    // longitudinal scattering: a spherical gaussian-like lobe, with a log form for narrow ones
+    // that would overflow otherwise
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    if v <= 0.1 {
+        (log_i0(a) - b - 1.0 / v + LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+fn np(dphi: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    // azimuthal scattering: a logistic lobe around the angle lobe p leaves the fiber at
+    let mut offset = dphi - phi(p, gamma_o, gamma_t);
+    while offset > PI {
+        offset -= 2.0 * PI;
+    }
+    while offset < -PI {
+        offset += 2.0 * PI;
+    }
+    trimmed_logistic(offset, s)
+}
+
+fn phi(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    let p = p as f64;
+    2.0 * p * gamma_t - 2.0 * gamma_o + p * PI
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+fn trimmed_logistic(x: f64, s: f64) -> f64 {
+    // the logistic distribution cut down to [-pi, pi]
+    let x = x.abs();
+    let logistic = (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2));
+    logistic / (logistic_cdf(PI, s) - logistic_cdf(-PI, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64) -> f64 {
+    let k = logistic_cdf(PI, s) - logistic_cdf(-PI, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(-PI, s)) - 1.0).ln();
+    x.clamp(-PI, PI)
+}
+
+fn i0(x: f64) -> f64 {
+    // modified bessel function of the first kind, from its series
+    let mut value = 0.0;
+    let mut x2i = 1.0;
+    let mut factorial: f64 = 1.0;
+    let mut four_i = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as f64;
+        }
+        value += x2i / (four_i * factorial * factorial);
+        x2i *= x * x;
+        four_i *= 4.0;
+    }
+    value
+}
+
+fn log_i0(x: f64) -> f64 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        i0(x).ln()
+    }
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{random_f64, seed_random};
+    use crate::sampler::SamplerType;
+    use crate::vector::Point3;
+
+    fn throughputs(hair: &Hair) -> Vec<Color> {
+        // scatter rays arriving from all over the front of a fiber along x, facing +z, at every
+        // point across it
+        seed_random(9);
+        let mut sampler = SamplerType::Independent.create(1);
+        let mut record = HitRecord::new();
+        record.geometric_normal = Vec3::new(0.0, 0.0, 1.0);
+        record.normal = record.geometric_normal;
+        record.front_face = true;
+        record.dpdu = Vec3::new(1.0, 0.0, 0.0);
+
+        let mut weights = Vec::new();
+        for s in 0..20000 {
+            sampler.start_pixel_sample(0, 0, s);
+            record.v = random_f64();
+            let d = Vec3::new(2.0 * random_f64() - 1.0, 2.0 * random_f64() - 1.0, -0.05 - random_f64());
+            if let Some((attenuation, _)) = hair.scatter(Ray::new(Point3::new(0.0, 0.0, 1.0), d), &record, sampler.as_mut()) {
+                weights.push(attenuation);
+            }
+        }
+        weights
+    }
+
+    #[test]
+    fn white_furnace() {
+        // a hair that absorbs nothing scatters all the light that hits it and never more, for
+        // smooth and rough fibers alike
+        for (beta_m, beta_n, alpha) in [(0.3, 0.3, 2.0), (0.05, 0.1, 0.0), (0.9, 0.9, 5.0)] {
+            let hair = Hair::new(Color::new(0.0, 0.0, 0.0), beta_m, beta_n, alpha);
+            let weights = throughputs(&hair);
+            assert!(weights.len() > 19000, "only {} scattered", weights.len());
+
+            let mut mean = 0.0;
+            for w in &weights {
+                assert!(w.x() <= 1.0 + 1e-9 && w.y() <= 1.0 + 1e-9 && w.z() <= 1.0 + 1e-9);
+                mean += luminance(*w) / weights.len() as f64;
+            }
+            assert!(mean > 0.999, "{}", mean);
+        }
+    }
+
+    #[test]
+    fn pigment_absorbs() {
+        let weights = throughputs(&Hair::from_melanin(1.3, 0.0, 0.3, 0.3, 2.0));
+        let mean = weights.iter().map(|&w| luminance(w)).sum::<f64>() / weights.len() as f64;
+        assert!(mean < 0.9, "{}", mean);
+        // and takes more blue than red
+        assert!(weights.iter().all(|w| w.z() <= w.x() + 1e-9));
+    }
+}
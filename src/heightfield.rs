@@ -0,0 +1,333 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::image::Image;
+use crate::interval::Interval;
+use crate::material::{Material, MIN_SHADING_COS};
+use crate::ray::Ray;
+use crate::triangle::intersect_triangle;
+use crate::vector::{cross, dot, unit_vector, Point3, Vec3};
+
+// terrain from a regular grid of heights, stretched over the box from `min` to `min + size`. the
+// grid runs along x and z, and heights from 0 to 1 are scaled up to the box's height. each cell
+// between four samples is split into two triangles, shaded with normals interpolated from the
+// samples. u and v run from 0 to 1 along x and z
+pub struct Heightfield {
+    nx: usize,
+    nz: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vec3>,
+    min: Point3,
+    cell: Vec3,
+    bbox: Aabb,
+    mat: Box<dyn Material>,
+}
+
+impl Heightfield {
+    pub fn new(nx: usize, nz: usize, heights: Vec<f64>, min: Point3, size: Vec3, mat: impl Material + 'static) -> Self {
+        assert!(nx >= 2 && nz >= 2 && heights.len() == nx * nz, "Could not fit the heights to the grid size.");
+
+        let heights: Vec<f64> = heights.iter().map(|h| min.y() + size.y() * h).collect();
+        let cell = Vec3::new(size.x() / (nx - 1) as f64, 0.0, size.z() / (nz - 1) as f64);
+
+        // normals from central differences, or one sided at the edges
+        let height = |i: usize, j: usize| heights[j * nx + i];
+        let mut normals = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let (i0, i1) = (i.saturating_sub(1), (i + 1).min(nx - 1));
+                let (j0, j1) = (j.saturating_sub(1), (j + 1).min(nz - 1));
+                let slope_x = (height(i1, j) - height(i0, j)) / ((i1 - i0) as f64 * cell.x());
+                let slope_z = (height(i, j1) - height(i, j0)) / ((j1 - j0) as f64 * cell.z());
+                normals.push(unit_vector(Vec3::new(-slope_x, 1.0, -slope_z)));
+            }
+        }
+
+        let lowest = heights.iter().cloned().fold(f64::INFINITY, f64::min);
+        let highest = heights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        // padded a little, since a ray running exactly along the grid's edge lies on the box's
+        // boundary, where the slab test can't tell it's inside
+        let bbox = Aabb::from_points(
+            Point3::new(min.x(), lowest, min.z()),
+            Point3::new(min.x() + size.x(), highest, min.z() + size.z()),
+        );
+        let bbox = Aabb::new(bbox.x.expand(0.0002), bbox.y.expand(0.0002), bbox.z.expand(0.0002));
+
+        Self { nx, nz, heights, normals, min, cell, bbox, mat: Box::new(mat) }
+    }
+
+    pub fn load_png(filename: &str, min: Point3, size: Vec3, mat: impl Material + 'static) -> io::Result<Self> {
+        // a grayscale image, such as a 16 bit DEM, with its rows running along z from the top down
+        let image = Image::load_png(filename)?;
+        let (nx, nz) = (image.width(), image.height());
+
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                heights.push(image.color(i, j).x());
+            }
+        }
+
+        Ok(Self::new(nx, nz, heights, min, size, mat))
+    }
+
+    pub fn load_raw(filename: &str, min: Point3, size: Vec3, mat: impl Material + 'static) -> io::Result<Self> {
+        // a text header followed by raw little-endian f32 heights, with x varying fastest:
+        //
+        //     RTHEIGHT
+        //     <nx> <nz>
+        //     <nx * nz floats>
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut r = BufReader::new(File::open(filename)?);
+
+        let mut line = String::new();
+        r.read_line(&mut line)?;
+        if line.trim() != "RTHEIGHT" {
+            return Err(invalid("not a height grid"));
+        }
+
+        line.clear();
+        r.read_line(&mut line)?;
+        let sizes: Vec<usize> = line
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid("bad height grid size"))?;
+        if sizes.len() != 2 || sizes[0] < 2 || sizes[1] < 2 {
+            return Err(invalid("bad height grid size"));
+        }
+
+        let (nx, nz) = (sizes[0], sizes[1]);
+        let mut bytes = vec![0; nx * nz * 4];
+        r.read_exact(&mut bytes)?;
+        let heights = bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64).collect();
+
+        Ok(Self::new(nx, nz, heights, min, size, mat))
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point3 {
+        Point3::new(self.min.x() + i as f64 * self.cell.x(), self.heights[j * self.nx + i], self.min.z() + j as f64 * self.cell.z())
+    }
+
+    fn hit_cell(&self, r: &Ray, ray_t: Interval, i: usize, j: usize) -> Option<HitRecord<'_>> {
+        // the cell's two triangles, each wound so that its normal points up
+        let corners = [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)];
+        let mut closest: Option<HitRecord> = None;
+
+        for [a, b, c] in [[corners[0], corners[2], corners[1]], [corners[1], corners[2], corners[3]]] {
+            let max = closest.as_ref().map_or(ray_t.max, |record| record.t);
+            let (p0, p1, p2) = (self.vertex(a.0, a.1), self.vertex(b.0, b.1), self.vertex(c.0, c.1));
+            let Some((t, b1, b2)) = intersect_triangle(r, Interval::new(ray_t.min, max), p0, p1, p2) else {
+                continue;
+            };
+
+            let b0 = 1.0 - b1 - b2;
+            let normal = |v: (usize, usize)| self.normals[v.1 * self.nx + v.0];
+            let mut shading_normal = unit_vector(b0 * normal(a) + b1 * normal(b) + b2 * normal(c));
+
+            // on steep ground the interpolated normal can lean past the triangle's own, which would
+            // shade the cell as if seen from underneath, so tilt it back just far enough
+            let outward_normal = unit_vector(cross(p1 - p0, p2 - p0));
+            let cos_theta = dot(&shading_normal, &outward_normal);
+            if cos_theta < MIN_SHADING_COS {
+                shading_normal = unit_vector(shading_normal + (MIN_SHADING_COS - cos_theta) * outward_normal);
+            }
+
+            let mut rec = HitRecord::new();
+            rec.t = t;
+            rec.p = r.at(t);
+            rec.set_face_normal(r, &outward_normal);
+            rec.set_shading_normal(&shading_normal);
+            rec.u = (rec.p.x() - self.min.x()) / (self.cell.x() * (self.nx - 1) as f64);
+            rec.v = (rec.p.z() - self.min.z()) / (self.cell.z() * (self.nz - 1) as f64);
+            rec.dpdu = Vec3::new(self.cell.x() * (self.nx - 1) as f64, 0.0, 0.0);
+            rec.dpdv = Vec3::new(0.0, 0.0, self.cell.z() * (self.nz - 1) as f64);
+            rec.mat = Some(&(*self.mat));
+
+            if rec.is_opaque(r) {
+                closest = Some(rec);
+            }
+        }
+
+        closest
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // the walk only covers the part of the ray inside the bounding box, but hits are checked
+        // against the whole interval, since flat ground can lie exactly on the box's boundary
+        let clipped = self.bbox.clip(r, ray_t)?;
+        let (o, d) = (r.origin(), r.direction());
+
+        // walk the cells under the ray in order with a 2D DDA. the first cell with a hit has the
+        // closest one, since each cell's triangles lie within its own column
+        let start = r.at(clipped.min);
+        let gx = (start.x() - self.min.x()) / self.cell.x();
+        let gz = (start.z() - self.min.z()) / self.cell.z();
+        let mut i = (gx.floor().max(0.0) as usize).min(self.nx - 2);
+        let mut j = (gz.floor().max(0.0) as usize).min(self.nz - 2);
+
+        let (mut next_x, delta_x) = boundary(d.x(), o.x(), self.min.x(), self.cell.x(), i);
+        let (mut next_z, delta_z) = boundary(d.z(), o.z(), self.min.z(), self.cell.z(), j);
+
+        let mut t = clipped.min;
+        loop {
+            // skip cells the ray passes entirely above or below
+            let cell_end = next_x.min(next_z).min(clipped.max);
+            let (y0, y1) = (r.at(t).y(), r.at(cell_end).y());
+            let corners = [self.vertex(i, j), self.vertex(i + 1, j), self.vertex(i, j + 1), self.vertex(i + 1, j + 1)];
+            let lowest = corners.iter().map(|p| p.y()).fold(f64::INFINITY, f64::min);
+            let highest = corners.iter().map(|p| p.y()).fold(f64::NEG_INFINITY, f64::max);
+
+            if y0.min(y1) <= highest && y0.max(y1) >= lowest {
+                if let Some(rec) = self.hit_cell(r, ray_t, i, j) {
+                    return Some(rec);
+                }
+            }
+
+            if cell_end >= clipped.max {
+                return None;
+            }
+
+            t = cell_end;
+            if next_x < next_z {
+                if d.x() > 0.0 && i + 2 < self.nx {
+                    i += 1;
+                } else if d.x() < 0.0 && i > 0 {
+                    i -= 1;
+                } else {
+                    return None;
+                }
+                next_x += delta_x;
+            } else {
+                if d.z() > 0.0 && j + 2 < self.nz {
+                    j += 1;
+                } else if d.z() < 0.0 && j > 0 {
+                    j -= 1;
+                } else {
+                    return None;
+                }
+                next_z += delta_z;
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
+
+fn boundary(d: f64, o: f64, min: f64, cell: f64, index: usize) -> (f64, f64) {
+    // the ray's distance to the next cell boundary it crosses along one axis, from cell `index`,
+    // and the distance between boundaries
+    if d > 0.0 {
+        ((min + (index + 1) as f64 * cell - o) / d, cell / d)
+    } else if d < 0.0 {
+        ((min + index as f64 * cell - o) / d, -cell / d)
+    } else {
+        (f64::INFINITY, f64::INFINITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::{random_f64, seed_random, INFINITY};
+    use crate::material::Lambertian;
+    use crate::vector::Color;
+
+    fn terrain() -> Heightfield {
+        // 8 by 6 cells of 0.5, over x from -1 to 3 and z from -2 to 1
+        seed_random(11);
+        let heights = (0..9 * 7).map(|_| random_f64()).collect();
+        let white = Lambertian::new(Color::new(1.0, 1.0, 1.0));
+        Heightfield::new(9, 7, heights, Point3::new(-1.0, 0.0, -2.0), Vec3::new(4.0, 1.0, 3.0), white)
+    }
+
+    fn brute_force(field: &Heightfield, r: &Ray, ray_t: Interval) -> Option<f64> {
+        (0..field.nz - 1)
+            .flat_map(|j| (0..field.nx - 1).map(move |i| (i, j)))
+            .filter_map(|(i, j)| field.hit_cell(r, ray_t, i, j))
+            .map(|rec| rec.t)
+            .min_by(|a, b| a.total_cmp(b))
+    }
+
+    fn check(field: &Heightfield, origin: Point3, direction: Vec3) -> Option<f64> {
+        let ray_t = Interval::new(0.001, INFINITY);
+        let expected = brute_force(field, &Ray::new(origin, direction), ray_t);
+        let t = field.hit(&Ray::new(origin, direction), ray_t).map(|rec| rec.t);
+        let ray = (origin.x(), origin.y(), origin.z(), direction.x(), direction.y(), direction.z());
+        match (t, expected) {
+            (Some(t), Some(expected)) => assert!((t - expected).abs() < 1e-9, "{} != {} for {:?}", t, expected, ray),
+            _ => assert_eq!(t, expected, "for {:?}", ray),
+        }
+        t
+    }
+
+    #[test]
+    fn vertical_rays() {
+        let field = terrain();
+        for _ in 0..200 {
+            let (x, z) = (-1.0 + 4.0 * random_f64(), -2.0 + 3.0 * random_f64());
+            assert!(check(&field, Point3::new(x, 2.0, z), Vec3::new(0.0, -1.0, 0.0)).is_some());
+            // and from underneath, which hits the ground's back
+            assert!(check(&field, Point3::new(x, -1.0, z), Vec3::new(0.0, 1.0, 0.0)).is_some());
+        }
+
+        // straight down the grid's corners and the lines between cells
+        for i in 0..9 {
+            for j in 0..7 {
+                let (x, z) = (-1.0 + 0.5 * i as f64, -2.0 + 0.5 * j as f64);
+                check(&field, Point3::new(x, 2.0, z), Vec3::new(0.0, -1.0, 0.0));
+                check(&field, Point3::new(x, 2.0, z + 0.25), Vec3::new(0.0, -1.0, 0.0));
+            }
+        }
+    }
+
+    #[test]
+    fn rays_along_grid_lines() {
+        let field = terrain();
+        for k in 0..9 {
+            let x = -1.0 + 0.5 * k as f64;
+            check(&field, Point3::new(x, 1.5, -3.0), Vec3::new(0.0, -0.3, 1.0));
+            check(&field, Point3::new(x, 1.5, 2.0), Vec3::new(0.0, -0.3, -1.0));
+        }
+        for k in 0..7 {
+            let z = -2.0 + 0.5 * k as f64;
+            check(&field, Point3::new(-2.0, 1.5, z), Vec3::new(1.0, -0.2, 0.0));
+            check(&field, Point3::new(4.0, 1.5, z), Vec3::new(-1.0, -0.2, 0.0));
+        }
+        // and diagonally through the grid's vertices
+        check(&field, Point3::new(-1.5, 1.2, -2.5), Vec3::new(1.0, -0.1, 1.0));
+        check(&field, Point3::new(3.5, 1.2, 1.5), Vec3::new(-1.0, -0.1, -1.0));
+    }
+
+    #[test]
+    fn rays_entering_from_each_side() {
+        let field = terrain();
+        let mut hits = 0;
+        for _ in 0..200 {
+            let (x, y, z) = (-1.0 + 4.0 * random_f64(), 1.5 * random_f64(), -2.0 + 3.0 * random_f64());
+            let target = Point3::new(-1.0 + 4.0 * random_f64(), 0.5 * random_f64(), -2.0 + 3.0 * random_f64());
+            for origin in [Point3::new(-3.0, y, z), Point3::new(5.0, y, z), Point3::new(x, y, -4.0), Point3::new(x, y, 3.0)] {
+                hits += check(&field, origin, target - origin).is_some() as usize;
+            }
+        }
+        assert!(hits > 400);
+    }
+
+    #[test]
+    fn random_rays_match_brute_force() {
+        let field = terrain();
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = Point3::new(-3.0 + 8.0 * random_f64(), -1.0 + 3.0 * random_f64(), -4.0 + 7.0 * random_f64());
+            let direction = Vec3::new(random_f64() - 0.5, random_f64() - 0.5, random_f64() - 0.5);
+            hits += check(&field, origin, direction).is_some() as usize;
+        }
+        assert!(hits > 100);
+    }
+}
@@ -1,15 +1,34 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::aabb::Aabb;
+use crate::common::{hash, INFINITY};
 use crate::material::Material;
 use crate::vector::{self, Point3, Vec3};
 use crate::ray::Ray;
 use crate::interval::Interval;
 
-// #[derive(Copy, Clone)]
+// next surface id not yet handed out by reserve_surfaces
+static NEXT_SURFACE: AtomicUsize = AtomicUsize::new(0);
+
+pub fn reserve_surfaces(count: usize) -> usize {
+    // a block of `count` surface ids no other shape uses, for HitRecord::surface. returns the first
+    NEXT_SURFACE.fetch_add(count, Ordering::Relaxed)
+}
+
+#[derive(Clone)]
 pub struct HitRecord<'a> {
     pub p: Point3,
-    pub normal: Vec3,
+    pub normal: Vec3, // shading normal, which normal and bump maps may tilt away from the surface's
+    pub geometric_normal: Vec3, // true surface normal; both normals face against the ray
+    pub dpdu: Vec3, // tangent frame: how the point moves with the texture coordinates
+    pub dpdv: Vec3,
     pub mat: Option<&'a dyn Material>,
     pub t: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
+    pub primitive: usize, // which of a shape's primitives was hit, for shapes with data per primitive
+    pub surface: Option<usize>, // unique id of the surface hit, carried by rays scattered from it
 }
 
 impl<'a> HitRecord<'a> {
@@ -23,9 +42,16 @@ impl<'a> HitRecord<'a> {
         Self {
             p: Point3::new(0.0, 0.0, 0.0),
             normal: Vec3::new(0.0, 0.0, 0.0),
+            geometric_normal: Vec3::new(0.0, 0.0, 0.0),
+            dpdu: Vec3::new(0.0, 0.0, 0.0),
+            dpdv: Vec3::new(0.0, 0.0, 0.0),
             mat: None, 
             t: 0.0,
-            front_face: false
+            u: 0.0,
+            v: 0.0,
+            front_face: false,
+            primitive: 0,
+            surface: None,
         }
     }
 
@@ -34,7 +60,39 @@ impl<'a> HitRecord<'a> {
         // NOTE: the parameter `outward_normal` is assumed to have unit length
 
         self.front_face = vector::dot(&r.direction(), &outward_normal) < 0.0;
-        self.normal = if self.front_face { *outward_normal } else { -outward_normal }
+        self.normal = if self.front_face { *outward_normal } else { -outward_normal };
+        self.geometric_normal = self.normal;
+    }
+
+    pub fn set_shading_normal(&mut self, outward_normal: &Vec3) {
+        // flips the shading normal to the same side as the geometric one, so front_face holds for both
+        // NOTE: the parameter `outward_normal` is assumed to have unit length
+
+        self.normal = if self.front_face { *outward_normal } else { -outward_normal };
+    }
+
+    pub fn is_opaque(&self, r: &Ray) -> bool {
+        // whether the material's opacity lets this hit stop the ray. fractional opacity is decided
+        // by hashing the ray and hit point rather than drawing a random number, so asking twice
+        // about the same hit gives the same answer
+        let opacity = match self.mat {
+            Some(mat) => mat.opacity(self.u, self.v, &self.p),
+            None => 1.0,
+        };
+        if opacity >= 1.0 {
+            return true;
+        }
+        if opacity <= 0.0 {
+            return false;
+        }
+
+        let (o, d) = (r.origin(), r.direction());
+        let mut h = 0;
+        for x in [o.x(), o.y(), o.z(), d.x(), d.y(), d.z(), self.p.x(), self.p.y(), self.p.z()] {
+            h = hash(h ^ x.to_bits());
+        }
+
+        ((h >> 11) as f64 / (1u64 << 53) as f64) < opacity
     }
 
     // pub fn update_mat(&mut self, mat: &'a dyn Material) {
@@ -42,6 +100,58 @@ impl<'a> HitRecord<'a> {
     // }
 }
 
+// a stretch of the ray inside a solid object, from where it enters to where it leaves. a ray that
+// starts inside enters at minus infinity, and one that is still inside at the end of the interval
+// leaves at infinity; those ends have no material
+#[derive(Clone)]
+pub struct HitInterval<'a> {
+    pub enter: HitRecord<'a>,
+    pub exit: HitRecord<'a>,
+}
+
+// most boundaries crossed while listing a ray's hit intervals
+const MAX_INTERVAL_HITS: usize = 64;
+
 pub trait Hittable {
     fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>;
+
+    fn bounding_box(&self) -> Aabb;
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        // the fraction of light that gets along the ray over ray_t, for shadow and visibility
+        // queries. surfaces block it outright; media override this to let some of it through
+        if self.hit(r, ray_t).is_some() { 0.0 } else { 1.0 }
+    }
+
+    fn hit_intervals(&self, r: &Ray, ray_t: Interval) -> Vec<HitInterval<'_>> {
+        // every stretch of the ray inside the object, in order. only makes sense for closed
+        // objects; this finds them by stepping from hit to hit and telling entries from exits by
+        // which side of the surface was hit
+        let mut intervals = Vec::new();
+        let mut enter: Option<HitRecord> = None;
+        let mut t = ray_t.min;
+
+        for _ in 0..MAX_INTERVAL_HITS {
+            let Some(record) = self.hit(r, Interval::new(t, ray_t.max)) else {
+                break;
+            };
+            t = record.t;
+
+            if record.front_face {
+                enter.get_or_insert(record);
+            } else {
+                let mut start = HitRecord::new();
+                start.t = -INFINITY;
+                intervals.push(HitInterval { enter: enter.take().unwrap_or(start), exit: record });
+            }
+        }
+
+        if let Some(enter) = enter {
+            let mut end = HitRecord::new();
+            end.t = INFINITY;
+            intervals.push(HitInterval { enter, exit: end });
+        }
+
+        intervals
+    }
 }
\ No newline at end of file
@@ -1,21 +1,28 @@
 use std::vec::Vec;
 
+use crate::aabb::{self, Aabb};
 use crate::{hittable::{HitRecord, Hittable}, ray::Ray};
 use crate::interval::Interval;
 
 pub struct HittableList {
     objects: Vec<Box<dyn Hittable>>,
+    bbox: Aabb,
 }
 
 impl HittableList {
     pub fn new() -> Self {
-        Self { objects: Vec::new()}
+        Self { objects: Vec::new(), bbox: aabb::EMPTY }
     }
 
     pub fn add(&mut self, object: impl Hittable + 'static) {
+        self.bbox = Aabb::surrounding(&self.bbox, &object.bounding_box());
         self.objects.push(Box::new(object));
         // self.objects.insert(0, Box::new(object));
     }
+
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.objects
+    }
 }
 
 impl Hittable for HittableList {
@@ -52,4 +59,20 @@ impl Hittable for HittableList {
 
         return None;
     }
+
+    fn transmittance(&self, r: &Ray, ray_t: Interval) -> f64 {
+        let mut transmittance = 1.0;
+        for object in self.objects.iter() {
+            transmittance *= object.transmittance(r, ray_t);
+            if transmittance == 0.0 {
+                break;
+            }
+        }
+
+        transmittance
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
 }
\ No newline at end of file
@@ -0,0 +1,70 @@
+use std::fs::File;
+use std::io;
+
+use crate::vector::Color;
+
+// an image loaded into memory, with every channel normalized to [0, 1]
+pub struct Image {
+    width: usize,
+    height: usize,
+    channels: usize,
+    data: Vec<f32>,
+}
+
+impl Image {
+    pub fn load_png(filename: &str) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(File::open(filename)?);
+        // palettes and bit depths below 8 are expanded; 16 bit images keep their full precision
+        decoder.set_transformations(png::Transformations::EXPAND);
+
+        let mut reader = decoder.read_info().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let bytes = &buf[..info.buffer_size()];
+
+        let channels = info.color_type.samples();
+        let data = match info.bit_depth {
+            png::BitDepth::Sixteen => bytes
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as f32 / 65535.0)
+                .collect(),
+            _ => bytes.iter().map(|&b| b as f32 / 255.0).collect(),
+        };
+
+        Ok(Self { width: info.width as usize, height: info.height as usize, channels, data })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn color(&self, x: usize, y: usize) -> Color {
+        // gray images are returned as gray colors; any alpha channel is ignored
+        let i = (y * self.width + x) * self.channels;
+        match self.channels {
+            1 | 2 => Color::new(self.data[i] as f64, self.data[i] as f64, self.data[i] as f64),
+            _ => Color::new(self.data[i] as f64, self.data[i + 1] as f64, self.data[i + 2] as f64),
+        }
+    }
+
+    pub fn bilinear(&self, x: f64, y: f64) -> Color {
+        // interpolates between pixel centers, where x and y are in pixel units and wrap around
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let wrap = |a: f64, n: usize| (a as i64).rem_euclid(n as i64) as usize;
+        let (xa, xb) = (wrap(x0, self.width), wrap(x0 + 1.0, self.width));
+        let (ya, yb) = (wrap(y0, self.height), wrap(y0 + 1.0, self.height));
+
+        (1.0 - ty) * ((1.0 - tx) * self.color(xa, ya) + tx * self.color(xb, ya))
+            + ty * ((1.0 - tx) * self.color(xa, yb) + tx * self.color(xb, yb))
+    }
+}
@@ -1,5 +1,6 @@
 use crate::common::INFINITY;
 
+#[derive(Copy, Clone)]
 pub struct Interval {
     pub min: f64,
     pub max: f64,
@@ -10,6 +11,11 @@ impl Interval {
         Self {min, max}
     }
 
+    pub fn enclosing(a: &Interval, b: &Interval) -> Self {
+        // the tightest interval containing both a and b
+        Self { min: a.min.min(b.min), max: a.max.max(b.max) }
+    }
+
     pub fn size(&self) -> f64 {
         self.max - self.min
     }
@@ -31,6 +37,11 @@ impl Interval {
         
         return x;
     }
+
+    pub fn expand(&self, delta: f64) -> Self {
+        let padding = delta / 2.0;
+        Self::new(self.min - padding, self.max + padding)
+    }
 }
 
 pub const EMPTY: Interval = Interval { min: INFINITY, max: -INFINITY };
@@ -1,21 +1,197 @@
+use std::env;
+use std::process;
+use std::sync::Arc;
+
+use aabb::Aabb;
+use bvh::BvhNode;
 use camera::Camera;
-use common::{random_f64, random_range_f64};
+use capsule::Capsule;
+use cone::Cone;
+use curve::{Curve, CurveType, Curves};
+use constant_medium::ConstantMedium;
+use csg::{Csg, CsgOperation};
+use cylinder::Cylinder;
+use disk::Disk;
+use displacement::DisplacedMesh;
+use common::{random_f64, random_range_f64, seed_random, PI};
+use filter::FilterType;
+use grid_medium::{GridMedium, VoxelGrid};
+use hair::Hair;
+use heightfield::Heightfield;
 use hittable_list::HittableList;
-use material::{Dielectric, Lambertian, Metal};
+use mesh::{Mesh, TriangleMesh};
+use material::{
+    BumpMap, Coated, Conductor, Cutout, Dielectric, DiffuseLight, DispersiveDielectric, HenyeyGreenstein, Isotropic,
+    Lambertian, Metal, MixMaterial, NormalMap, Principled, RoughDielectric,
+};
+use perlin::Perlin;
+use plane::Plane;
+use point_cloud::{PointCloud, PointShape, Points};
+use quad::{make_box, Quad};
+use sampler::SamplerType;
+use sdf::{Mandelbulb, Repeat, SdfBox, SdfObject, SdfSphere, SdfTorus, SmoothUnion, Translate, Twist};
+use spectrum::Ior;
 use sphere::Sphere;
-use vector::{Color, Point3, Vec3};
+use subdivision::{SubdivisionScheme, SubdivisionSurface};
+use subsurface::Subsurface;
+use torus::Torus;
+use texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+use triangle::Triangle;
+use vector::{cross, dot, sample_unit_vector, unit_vector, Color, Point3, Vec3};
 
+mod aabb;
+mod bvh;
 mod camera;
+mod capsule;
+mod checkpoint;
 mod common;
+mod cone;
+mod constant_medium;
+mod csg;
+mod curve;
+mod cylinder;
+mod disk;
+mod displacement;
+mod film;
+mod filter;
+mod grid_medium;
+mod hair;
+mod heightfield;
 mod hittable;
 mod hittable_list;
+mod image;
 mod interval;
 mod material;
+mod mesh;
+mod microfacet;
+mod onb;
+mod perlin;
+mod plane;
+mod point_cloud;
+mod quad;
 mod ray;
+mod sampler;
+mod sdf;
+mod spectrum;
 mod sphere;
+mod subdivision;
+mod subsurface;
+mod texture;
+mod torus;
+mod triangle;
 mod vector;
 
-fn main() {
+struct Options {
+    scene: String,
+    texture: Option<String>,
+    normal_map: Option<String>,
+    grid: Option<String>,
+    heightfield: Option<String>,
+    mesh: Option<String>,
+    points: Option<String>,
+    image_width: i32,
+    samples_per_pixel: i32,
+    adaptive_threshold: f64,
+    min_samples_per_pixel: i32,
+    write_sample_map: bool,
+    sampler: SamplerType,
+    filter: FilterType,
+    filter_radius: Option<f64>,
+    progressive_samples: i32,
+    snapshot_interval: Option<f64>,
+    seed: u64,
+    checkpoint_file: Option<String>,
+    checkpoint_interval: f64,
+    resume: bool,
+    time_budget: Option<f64>,
+    sample_budget: Option<i64>,
+    target_noise: Option<f64>,
+    spectral: bool,
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        scene: String::from("spheres"),
+        texture: None,
+        normal_map: None,
+        grid: None,
+        heightfield: None,
+        mesh: None,
+        points: None,
+        image_width: 1200,
+        samples_per_pixel: 500,
+        adaptive_threshold: 0.0,
+        min_samples_per_pixel: 16,
+        write_sample_map: false,
+        sampler: SamplerType::Independent,
+        filter: FilterType::Box,
+        filter_radius: None,
+        progressive_samples: 0,
+        snapshot_interval: None,
+        seed: 0,
+        checkpoint_file: None,
+        checkpoint_interval: 600.0,
+        resume: false,
+        time_budget: None,
+        sample_budget: None,
+        target_noise: None,
+        spectral: false,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scene" => options.scene = parse_value(&arg, args.next()),
+            "--texture" => options.texture = Some(parse_value(&arg, args.next())),
+            "--normal-map" => options.normal_map = Some(parse_value(&arg, args.next())),
+            "--grid" => options.grid = Some(parse_value(&arg, args.next())),
+            "--heightfield" => options.heightfield = Some(parse_value(&arg, args.next())),
+            "--mesh" => options.mesh = Some(parse_value(&arg, args.next())),
+            "--points" => options.points = Some(parse_value(&arg, args.next())),
+            "--width" => options.image_width = parse_value(&arg, args.next()),
+            "--spp" => options.samples_per_pixel = parse_value(&arg, args.next()),
+            "--adaptive" => options.adaptive_threshold = parse_value(&arg, args.next()),
+            "--min-spp" => options.min_samples_per_pixel = parse_value(&arg, args.next()),
+            "--sample-map" => options.write_sample_map = true,
+            "--sampler" => options.sampler = parse_value(&arg, args.next()),
+            "--filter" => options.filter = parse_value(&arg, args.next()),
+            "--filter-radius" => options.filter_radius = Some(parse_value(&arg, args.next())),
+            "--progressive" => options.progressive_samples = parse_value(&arg, args.next()),
+            "--snapshot-interval" => options.snapshot_interval = Some(parse_value(&arg, args.next())),
+            "--seed" => options.seed = parse_value(&arg, args.next()),
+            "--checkpoint" => options.checkpoint_file = Some(parse_value(&arg, args.next())),
+            "--checkpoint-interval" => options.checkpoint_interval = parse_value(&arg, args.next()),
+            "--resume" => options.resume = true,
+            "--time-budget" => options.time_budget = Some(parse_value(&arg, args.next())),
+            "--sample-budget" => options.sample_budget = Some(parse_value(&arg, args.next())),
+            "--target-noise" => options.target_noise = Some(parse_value(&arg, args.next())),
+            "--spectral" => options.spectral = true,
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                process::exit(1);
+            }
+        }
+    }
+
+    if options.samples_per_pixel < 1 {
+        eprintln!("--spp must be at least 1");
+        process::exit(1);
+    }
+
+    options
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    match value.as_deref().map(str::parse) {
+        Some(Ok(v)) => v,
+        _ => {
+            eprintln!("Missing or invalid value for {}", flag);
+            process::exit(1);
+        }
+    }
+}
+
+fn random_spheres(options: &Options) -> (HittableList, Camera) {
     let mut world = HittableList::new();
 
     let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
@@ -56,11 +232,11 @@ fn main() {
 
     // Image
     let aspect_ratio = 16.0 / 9.0;
-    let image_width = 1200;
-    let samples_per_pixel = 500;
+    let image_width = options.image_width;
+    let samples_per_pixel = options.samples_per_pixel;
     let max_depth = 50;
 
-    let mut camera = Camera::new(
+    let camera = Camera::new(
         aspect_ratio,
         image_width,
         samples_per_pixel,
@@ -73,5 +249,1141 @@ fn main() {
         10.0,
     );
 
-    camera.render(world);
+    (world, camera)
+}
+
+fn metals(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    world.add(Sphere::new(Point3::new(-3.3, 1.0, 0.0), 1.0, Conductor::gold(0.1)));
+    world.add(Sphere::new(Point3::new(-1.1, 1.0, 0.0), 1.0, Conductor::copper(0.3)));
+    world.add(Sphere::new(Point3::new(1.1, 1.0, 0.0), 1.0, Conductor::silver(0.0)));
+
+    // brushed aluminium: much rougher across one tangent direction than the other
+    let brushed = Conductor::new(Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.270, 4.837), 0.1, 0.5);
+    world.add(Sphere::new(Point3::new(3.3, 1.0, 0.0), 1.0, brushed));
+
+    world.add(Sphere::new(Point3::new(0.0, 0.5, 2.5), 0.5, Conductor::aluminium(0.2)));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        30.0,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn glass(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.8, 0.3, 0.2));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    // clear glass getting progressively more frosted, then tinted glass of increasing roughness
+    for (i, roughness) in [0.0, 0.1, 0.25, 0.5].into_iter().enumerate() {
+        let x = -3.3 + 2.2 * i as f64;
+        world.add(Sphere::new(Point3::new(x, 1.0, 0.0), 1.0, RoughDielectric::new(1.5, roughness, Color::new(1.0, 1.0, 1.0))));
+
+        let tint = Color::new(0.3, 0.7, 0.5);
+        world.add(Sphere::new(Point3::new(x, 0.5, 2.5), 0.5, RoughDielectric::new(1.5, roughness, tint)));
+    }
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        30.0,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn principled(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let even: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(0.2, 0.3, 0.1)));
+    let odd: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(0.9, 0.9, 0.9)));
+    let checker = Principled::new(Arc::new(CheckerTexture::new(0.5, even, odd)));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, checker));
+
+    let red: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(0.8, 0.1, 0.1)));
+    let constant = |x: f64| -> Arc<dyn Texture> { Arc::new(SolidColor::gray(x)) };
+
+    // each row sweeps one parameter from 0 to 1, left to right: metallic, roughness, sheen,
+    // clearcoat and transmission
+    for row in 0..5 {
+        for column in 0..5 {
+            let mut material = Principled::new(red.clone());
+            material.roughness = constant(0.3);
+
+            let x = constant(column as f64 / 4.0);
+            match row {
+                0 => material.metallic = x,
+                1 => material.roughness = x,
+                2 => material.sheen = x,
+                3 => material.clearcoat = x,
+                _ => material.transmission = x,
+            }
+
+            let center = Point3::new(-2.4 + 1.2 * column as f64, 0.5, -2.4 + 1.2 * row as f64);
+            world.add(Sphere::new(center, 0.5, material));
+        }
+    }
+
+    // an image textured sphere, e.g. a color map exported alongside a glTF asset
+    if let Some(filename) = &options.texture {
+        let material = Principled::new(Arc::new(ImageTexture::new(filename)));
+        world.add(Sphere::new(Point3::new(0.0, 1.5, -4.5), 1.5, material));
+    }
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        35.0,
+        Point3::new(0.0, 6.0, 9.0),
+        Point3::new(0.0, 0.0, -0.5),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn layered(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    // car paint: colored base with metal flakes mixed in, under a smooth clear coat
+    let flakes = MixMaterial::new(
+        Lambertian::new(Color::new(0.6, 0.05, 0.05)),
+        Conductor::aluminium(0.4),
+        Arc::new(SolidColor::gray(0.25)),
+    );
+    world.add(Sphere::new(Point3::new(-3.3, 1.0, 0.0), 1.0, Coated::new(flakes, 1.5, 0.0, Color::new(1.0, 1.0, 1.0))));
+
+    // lacquered wood: amber varnish over a brown base
+    let wood = Lambertian::new(Color::new(0.45, 0.25, 0.1));
+    world.add(Sphere::new(Point3::new(-1.1, 1.0, 0.0), 1.0, Coated::new(wood, 1.5, 0.05, Color::new(0.9, 0.75, 0.5))));
+
+    // half gilded: gold and white plaster mixed by a checkerboard
+    let mask: Arc<dyn Texture> = Arc::new(CheckerTexture::new(
+        0.25,
+        Arc::new(SolidColor::gray(0.0)),
+        Arc::new(SolidColor::gray(1.0)),
+    ));
+    let gilded = MixMaterial::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)), Conductor::gold(0.2), mask);
+    world.add(Sphere::new(Point3::new(1.1, 1.0, 0.0), 1.0, gilded));
+
+    // varnished metal
+    world.add(Sphere::new(Point3::new(3.3, 1.0, 0.0), 1.0, Coated::new(Conductor::copper(0.5), 1.5, 0.0, Color::new(1.0, 1.0, 1.0))));
+
+    // a plain diffuse sphere, then the same under coats of increasing roughness
+    let blue = Color::new(0.1, 0.2, 0.6);
+    world.add(Sphere::new(Point3::new(-2.4, 0.5, 2.5), 0.5, Lambertian::new(blue)));
+    for (i, roughness) in [0.0, 0.15, 0.3, 0.6].into_iter().enumerate() {
+        let center = Point3::new(-1.2 + 1.2 * i as f64, 0.5, 2.5);
+        world.add(Sphere::new(center, 0.5, Coated::new(Lambertian::new(blue), 1.5, roughness, Color::new(1.0, 1.0, 1.0))));
+    }
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        30.0,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn bumps(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    // the same marble veins raised as bumps on diffuse, metal and coated surfaces
+    let marble: Arc<dyn Texture> = Arc::new(NoiseTexture::new(4.0));
+    let plaster = BumpMap::new(Lambertian::new(Color::new(0.8, 0.8, 0.8)), marble.clone(), 0.02);
+    world.add(Sphere::new(Point3::new(-3.3, 1.0, 0.0), 1.0, plaster));
+
+    let hammered = BumpMap::new(Conductor::copper(0.2), marble.clone(), 0.02);
+    world.add(Sphere::new(Point3::new(-1.1, 1.0, 0.0), 1.0, hammered));
+
+    // orange peel: the clear coat is bumpy, the paint under it isn't
+    let paint = Lambertian::new(Color::new(0.1, 0.2, 0.6));
+    let peel = BumpMap::new(Coated::new(paint, 1.5, 0.0, Color::new(1.0, 1.0, 1.0)), Arc::new(NoiseTexture::new(20.0)), 0.002);
+    world.add(Sphere::new(Point3::new(1.1, 1.0, 0.0), 1.0, peel));
+
+    let smooth = Conductor::gold(0.2);
+    match &options.normal_map {
+        Some(filename) => world.add(Sphere::new(Point3::new(3.3, 1.0, 0.0), 1.0, NormalMap::new(smooth, filename))),
+        None => world.add(Sphere::new(Point3::new(3.3, 1.0, 0.0), 1.0, smooth)),
+    }
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        30.0,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn cutouts(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    let holes = |scale: f64| -> Arc<dyn Texture> {
+        Arc::new(CheckerTexture::new(scale, Arc::new(SolidColor::gray(0.0)), Arc::new(SolidColor::gray(1.0))))
+    };
+
+    // a hollow shell with square holes, through which its inside shows
+    let shell = Cutout::new(Lambertian::new(Color::new(0.8, 0.3, 0.2)), holes(0.25));
+    world.add(Sphere::new(Point3::new(-2.2, 1.0, 0.0), 1.0, shell));
+
+    // gauze: fractional opacity lets part of the light through everywhere
+    let gauze = Cutout::new(Lambertian::new(Color::new(0.9, 0.9, 0.9)), Arc::new(NoiseTexture::new(4.0)));
+    world.add(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, gauze));
+
+    world.add(Sphere::new(Point3::new(2.2, 1.0, 0.0), 1.0, Conductor::gold(0.2)));
+
+    // a fence panel of two triangles in front of the gold sphere
+    let (a, b, c, d) = (
+        Point3::new(1.2, 0.0, 1.5),
+        Point3::new(3.2, 0.0, 1.5),
+        Point3::new(3.2, 2.0, 1.5),
+        Point3::new(1.2, 2.0, 1.5),
+    );
+    let fence = || Cutout::new(Lambertian::new(Color::new(0.2, 0.4, 0.2)), holes(0.15));
+    world.add(Triangle::with_uvs(a, b, c, [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)], fence()));
+    world.add(Triangle::with_uvs(a, c, d, [(0.0, 0.0), (1.0, 1.0), (0.0, 1.0)], fence()));
+
+    // a bush of ragged leaves
+    for _ in 0..300 {
+        let center = Point3::new(-3.5, 1.2, 3.0) + Vec3::random_range(-0.8, 0.8);
+        let p0 = center + 0.3 * Vec3::random_range(-1.0, 1.0);
+        let p1 = center + 0.3 * Vec3::random_range(-1.0, 1.0);
+        let p2 = center + 0.3 * Vec3::random_range(-1.0, 1.0);
+
+        let green = Color::new(0.1 + 0.1 * random_f64(), 0.3 + 0.3 * random_f64(), 0.05);
+        let leaf = Cutout::new(Lambertian::new(green), holes(0.1));
+        world.add(Triangle::new(p0, p1, p2, leaf));
+    }
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        30.0,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn fog(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    // the boundary's own material is never seen, only the medium inside it
+    let boundary = |center: Point3, radius: f64| Sphere::new(center, radius, Lambertian::new(Color::new(0.0, 0.0, 0.0)));
+
+    // white and black smoke
+    let white = Isotropic::new(Color::new(0.9, 0.9, 0.9));
+    world.add(ConstantMedium::new(boundary(Point3::new(-3.3, 1.0, 0.0), 1.0), 2.0, white));
+    let black = Isotropic::new(Color::new(0.1, 0.1, 0.1));
+    world.add(ConstantMedium::new(boundary(Point3::new(-1.1, 1.0, 0.0), 1.0), 2.0, black));
+
+    // murky water: a glass sphere with a forward scattering medium inside
+    let center = Point3::new(1.1, 1.0, 0.0);
+    world.add(Sphere::new(center, 1.0, Dielectric::new(1.33)));
+    let water = HenyeyGreenstein::new(Color::new(0.3, 0.7, 0.6), 0.6);
+    world.add(ConstantMedium::new(boundary(center, 1.0), 1.5, water));
+
+    world.add(Sphere::new(Point3::new(3.3, 1.0, 0.0), 1.0, Conductor::gold(0.1)));
+
+    // thin fog over the whole scene, camera included
+    let haze = HenyeyGreenstein::new(Color::new(0.9, 0.9, 0.9), 0.7);
+    world.add(ConstantMedium::new(boundary(Point3::new(0.0, 0.0, 0.0), 40.0), 0.02, haze));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        30.0,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn smoke(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    let grid = match &options.grid {
+        Some(filename) => VoxelGrid::load(filename).expect("Could not read voxel grid."),
+        None => smoke_plume(64),
+    };
+
+    // the grid's second channel glows orange, for fire at the base of the plume
+    let phase_function = HenyeyGreenstein::new(Color::new(1.0, 1.0, 1.0), 0.3);
+    let min = Point3::new(-1.5, 0.0, -1.5);
+    let max = Point3::new(1.5, 4.0, 1.5);
+    world.add(GridMedium::new(
+        grid,
+        min,
+        max,
+        20.0,
+        Color::new(0.7, 0.7, 0.7),
+        Color::new(27.0, 10.0, 2.7),
+        phase_function,
+    ));
+
+    world.add(Sphere::new(Point3::new(-3.0, 1.0, 1.0), 1.0, Conductor::silver(0.1)));
+    world.add(Sphere::new(Point3::new(3.0, 1.0, 1.0), 1.0, Lambertian::new(Color::new(0.2, 0.3, 0.6))));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        35.0,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 1.6, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn smoke_plume(n: usize) -> VoxelGrid {
+    // a turbulent column of smoke widening as it rises, with hot glowing gas near the bottom. the
+    // channels are density and emission strength
+    let noise = Perlin::new();
+    let mut data = Vec::with_capacity(n * n * n * 2);
+
+    for k in 0..n {
+        for j in 0..n {
+            for i in 0..n {
+                let p = Point3::new(i as f64, j as f64, k as f64) / n as f64;
+                let turbulence = noise.turb(&(6.0 * p), 5);
+
+                let radius = 0.1 + 0.3 * p.y();
+                let distance = ((p.x() - 0.5).powi(2) + (p.z() - 0.5).powi(2)).sqrt();
+                let inside = (1.0 - distance / radius).max(0.0);
+
+                let density = (inside * (0.3 + 1.5 * turbulence) * (1.0 - p.y())).clamp(0.0, 1.0);
+                let heat = (inside * (1.0 - p.y() / 0.3).max(0.0) * (0.5 + turbulence)).clamp(0.0, 1.0);
+
+                data.push(density as f32);
+                data.push(heat as f32);
+            }
+        }
+    }
+
+    VoxelGrid::new(n, n, n, 2, data)
+}
+
+fn subsurface(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    // the boundary's own material is never seen, only the medium inside it
+    let boundary = |center: Point3, radius: f64| Sphere::new(center, radius, Lambertian::new(Color::new(0.0, 0.0, 0.0)));
+
+    // opaque diffuse for comparison, then marble, skin and jade
+    world.add(Sphere::new(Point3::new(-3.3, 1.0, 0.0), 1.0, Lambertian::new(Color::new(0.8, 0.8, 0.8))));
+
+    let marble_center = Point3::new(-1.1, 1.0, 0.0);
+    let marble_color = Color::new(0.83, 0.79, 0.75);
+    let marble = Subsurface::new(boundary(marble_center, 1.0), 1.5, 0.1, marble_color, Color::new(0.2, 0.2, 0.2), 0.0);
+    world.add(marble);
+
+    // red light travels furthest through skin
+    let skin_color = Color::new(0.85, 0.6, 0.5);
+    let skin_path = Color::new(0.4, 0.15, 0.08);
+    world.add(Subsurface::new(boundary(Point3::new(1.1, 1.0, 0.0), 1.0), 1.4, 0.35, skin_color, skin_path, 0.0));
+
+    let jade_color = Color::new(0.3, 0.8, 0.45);
+    let jade_path = Color::new(0.5, 0.8, 0.5);
+    world.add(Subsurface::new(boundary(Point3::new(3.3, 1.0, 0.0), 1.0), 1.6, 0.05, jade_color, jade_path, 0.3));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        30.0,
+        Point3::new(0.0, 3.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn prism(options: &Options) -> (HittableList, Camera) {
+    // best rendered with --spectral, which splits light into its colors where it refracts
+    let mut world = HittableList::new();
+
+    let ground_material = Lambertian::new(Color::new(0.5, 0.5, 0.5));
+    world.add(Sphere::new(Point3::new(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+    // a triangular prism of dense flint glass lying along the x axis, built from triangles wound
+    // so that their normals face out
+    let corners = [Point3::new(0.0, 0.0, -0.7), Point3::new(0.0, 0.0, 0.7), Point3::new(0.0, 1.2, 0.0)];
+    let length = Vec3::new(1.6, 0.0, 0.0);
+    let center = Point3::new(0.0, 0.4, 0.0);
+    let mut face = |a: Point3, b: Point3, c: Point3| {
+        let outward = dot(&cross(b - a, c - a), &(a - center)) > 0.0;
+        let (b, c) = if outward { (b, c) } else { (c, b) };
+        world.add(Triangle::new(a, b, c, DispersiveDielectric::new(Ior::dense_flint(), 0.0)));
+    };
+    face(corners[0] - length, corners[1] - length, corners[2] - length);
+    face(corners[0] + length, corners[1] + length, corners[2] + length);
+    for i in 0..3 {
+        let (a, b) = (corners[i], corners[(i + 1) % 3]);
+        face(a - length, b - length, b + length);
+        face(a - length, b + length, a + length);
+    }
+
+    // a row of small lamps behind the prism, seen through it as spectra
+    for i in 0..5 {
+        let x = -1.2 + 0.6 * i as f64;
+        world.add(Sphere::new(Point3::new(x, 1.6, -3.0), 0.12, DiffuseLight::new(Color::new(12.0, 12.0, 12.0))));
+    }
+
+    // diamond, crown glass and a drop of water, then two metals whose color shifts towards
+    // grazing angles
+    world.add(Sphere::new(Point3::new(-3.0, 0.7, 1.0), 0.7, DispersiveDielectric::new(Ior::diamond(), 0.0)));
+    world.add(Sphere::new(Point3::new(-1.6, 0.4, 2.2), 0.4, DispersiveDielectric::new(Ior::bk7(), 0.0)));
+    world.add(Sphere::new(Point3::new(3.0, 0.7, 1.0), 0.7, DispersiveDielectric::new(Ior::water(), 0.0)));
+    world.add(Sphere::new(Point3::new(1.6, 0.4, 2.2), 0.4, DispersiveDielectric::new(Ior::Constant(1.5), 0.3)));
+    world.add(Sphere::new(Point3::new(-3.5, 1.0, -2.5), 1.0, Conductor::gold(0.1)));
+    world.add(Sphere::new(Point3::new(3.5, 1.0, -2.5), 1.0, Conductor::copper(0.1)));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        30.0,
+        Point3::new(0.0, 2.0, 12.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn shapes(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let even: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(0.2, 0.3, 0.1)));
+    let odd: Arc<dyn Texture> = Arc::new(SolidColor::new(Color::new(0.9, 0.9, 0.9)));
+    let checker = Arc::new(CheckerTexture::new(0.5, even, odd));
+    world.add(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Principled::new(checker)));
+
+    // an open box of colored quads
+    let red = Lambertian::new(Color::new(1.0, 0.2, 0.2));
+    let green = Lambertian::new(Color::new(0.2, 1.0, 0.2));
+    let blue = Lambertian::new(Color::new(0.2, 0.2, 1.0));
+    world.add(Quad::new(Point3::new(-4.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 3.0, 0.0), red));
+    world.add(Quad::new(Point3::new(-4.0, 0.0, -2.0), Vec3::new(3.0, 0.0, 0.0), Vec3::new(0.0, 3.0, 0.0), green));
+    world.add(Quad::new(Point3::new(-1.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 3.0), Vec3::new(0.0, 3.0, 0.0), blue));
+    world.add(make_box(Point3::new(-3.2, 0.0, -0.6), Point3::new(-2.2, 1.0, 0.4), || Conductor::copper(0.2)));
+
+    // disks standing up, lying flat and tilted
+    world.add(Disk::new(Point3::new(1.5, 1.0, -1.0), Vec3::new(0.0, 0.0, 1.0), 1.0, Conductor::gold(0.1)));
+    world.add(Disk::new(Point3::new(1.5, 0.01, 1.5), Vec3::new(0.0, 1.0, 0.0), 0.8, Lambertian::new(Color::new(0.9, 0.6, 0.1))));
+    world.add(Disk::new(Point3::new(3.8, 1.0, 0.0), Vec3::new(-1.0, 0.5, 1.0), 0.9, Lambertian::new(Color::new(0.2, 0.6, 0.8))));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        40.0,
+        Point3::new(0.0, 4.0, 9.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn cornell_box(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let red = || Lambertian::new(Color::new(0.65, 0.05, 0.05));
+    let white = || Lambertian::new(Color::new(0.73, 0.73, 0.73));
+    let green = || Lambertian::new(Color::new(0.12, 0.45, 0.15));
+    let light = DiffuseLight::new(Color::new(15.0, 15.0, 15.0));
+
+    world.add(Quad::new(Point3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), green()));
+    world.add(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), Vec3::new(0.0, 0.0, 555.0), red()));
+    world.add(Quad::new(Point3::new(343.0, 554.0, 332.0), Vec3::new(-130.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -105.0), light));
+    world.add(Quad::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 555.0), white()));
+    world.add(Quad::new(Point3::new(555.0, 555.0, 555.0), Vec3::new(-555.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -555.0), white()));
+    world.add(Quad::new(Point3::new(0.0, 0.0, 555.0), Vec3::new(555.0, 0.0, 0.0), Vec3::new(0.0, 555.0, 0.0), white()));
+
+    world.add(make_box(Point3::new(130.0, 0.0, 65.0), Point3::new(295.0, 165.0, 230.0), white));
+    world.add(make_box(Point3::new(265.0, 0.0, 295.0), Point3::new(430.0, 330.0, 460.0), white));
+
+    let mut camera = Camera::new(
+        1.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        40.0,
+        Point3::new(278.0, 278.0, -800.0),
+        Point3::new(278.0, 278.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+    camera.background = Some(Color::new(0.0, 0.0, 0.0));
+
+    (world, camera)
+}
+
+fn pipes(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    world.add(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+    // an open pipe lying on the ground, with flanges at both ends
+    let start = Point3::new(-4.0, 0.5, -1.0);
+    let end = Point3::new(-0.5, 0.5, -1.0);
+    world.add(Cylinder::new(start, end, 0.4, false, Conductor::aluminium(0.3)));
+    for center in [start, end] {
+        world.add(Torus::new(center, end - start, 0.45, 0.08, Conductor::copper(0.2)));
+    }
+
+    // a bolt: a wide head, the shaft and a pointed tip
+    let paint = || Principled::new(Arc::new(SolidColor::new(Color::new(0.8, 0.1, 0.1))));
+    world.add(Cylinder::new(Point3::new(1.0, 0.0, -1.0), Point3::new(1.0, 0.3, -1.0), 0.5, true, paint()));
+    world.add(Cylinder::new(Point3::new(1.0, 0.3, -1.0), Point3::new(1.0, 2.0, -1.0), 0.25, true, Conductor::silver(0.2)));
+    world.add(Cone::new(Point3::new(1.0, 2.0, -1.0), Point3::new(1.0, 2.4, -1.0), 0.25, false, Conductor::silver(0.2)));
+
+    // a traffic cone with a ring at its base, and a tilted ring standing on its edge
+    world.add(Cone::new(Point3::new(3.2, 0.0, -0.5), Point3::new(3.2, 1.8, -0.5), 0.6, true, Lambertian::new(Color::new(0.9, 0.4, 0.05))));
+    world.add(Torus::new(Point3::new(3.2, 0.1, -0.5), Vec3::new(0.0, 1.0, 0.0), 0.7, 0.1, Lambertian::new(Color::new(0.1, 0.1, 0.1))));
+    world.add(Torus::new(Point3::new(-1.5, 0.8, 1.5), Vec3::new(1.0, 0.2, 0.6), 0.6, 0.2, Conductor::gold(0.1)));
+
+    // capsules, as pills and as a handrail
+    let pill = || Principled::new(Arc::new(SolidColor::new(Color::new(0.2, 0.5, 0.9))));
+    world.add(Capsule::new(Point3::new(0.5, 0.25, 1.5), Point3::new(1.5, 0.25, 2.2), 0.25, pill()));
+    world.add(Capsule::new(Point3::new(2.2, 0.25, 1.8), Point3::new(2.4, 0.25, 0.9), 0.25, pill()));
+    world.add(Capsule::new(Point3::new(-4.5, 1.8, -2.5), Point3::new(4.5, 1.8, -2.5), 0.08, Conductor::aluminium(0.1)));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        40.0,
+        Point3::new(0.0, 4.0, 9.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn csg(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    world.add(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+    // the classic: a cube rounded off by a sphere, with three cylinders drilled through it
+    let center = Point3::new(-2.2, 1.0, 0.0);
+    let corner = Vec3::new(0.75, 0.75, 0.75);
+    let red = || Lambertian::new(Color::new(0.8, 0.15, 0.1));
+    let blue = || Lambertian::new(Color::new(0.1, 0.3, 0.8));
+    let rounded = Csg::new(
+        CsgOperation::Intersection,
+        make_box(center - corner, center + corner, red),
+        Sphere::new(center, 1.0, blue()),
+    );
+    let drill = |axis: Vec3| Cylinder::new(center - 1.2 * axis, center + 1.2 * axis, 0.45, true, Conductor::gold(0.2));
+    let drills = Csg::new(
+        CsgOperation::Union,
+        Csg::new(CsgOperation::Union, drill(Vec3::new(1.0, 0.0, 0.0)), drill(Vec3::new(0.0, 1.0, 0.0))),
+        drill(Vec3::new(0.0, 0.0, 1.0)),
+    );
+    world.add(Csg::new(CsgOperation::Difference, rounded, drills));
+
+    // a glass sphere with a bite taken out of it, which still refracts as one solid
+    let bitten = Csg::new(
+        CsgOperation::Difference,
+        Sphere::new(Point3::new(0.2, 1.0, 0.0), 1.0, Dielectric::new(1.5)),
+        Sphere::new(Point3::new(0.8, 1.5, 0.7), 0.7, Dielectric::new(1.5)),
+    );
+    world.add(bitten);
+
+    // a lens: where two spheres overlap, next to the union of a torus and a capsule through it
+    let lens = Csg::new(
+        CsgOperation::Intersection,
+        Sphere::new(Point3::new(2.0, 1.3, -1.6), 2.0, Conductor::silver(0.05)),
+        Sphere::new(Point3::new(2.0, 1.3, 1.6), 2.0, Conductor::silver(0.05)),
+    );
+    world.add(lens);
+    let ring = Csg::new(
+        CsgOperation::Union,
+        Torus::new(Point3::new(3.8, 0.8, 1.2), Vec3::new(0.0, 0.0, 1.0), 0.6, 0.15, Conductor::copper(0.2)),
+        Capsule::new(Point3::new(3.8, 0.1, 1.2), Point3::new(3.8, 1.5, 1.2), 0.12, Lambertian::new(Color::new(0.9, 0.9, 0.9))),
+    );
+    world.add(ring);
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        40.0,
+        Point3::new(0.0, 3.5, 8.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn sdf(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    world.add(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+    // a mandelbulb, sitting on an ordinary sphere
+    let bulb_center = Point3::new(-2.4, 1.6, 0.0);
+    let bulb = Mandelbulb::new(bulb_center, 1.0, 8.0, 12);
+    let bulb_box = Aabb::from_points(bulb_center - Vec3::new(1.3, 1.3, 1.3), bulb_center + Vec3::new(1.3, 1.3, 1.3));
+    world.add(SdfObject::new(bulb, bulb_box, Principled::new(Arc::new(SolidColor::new(Color::new(0.9, 0.6, 0.3))))));
+    world.add(Sphere::new(Point3::new(-2.4, 0.3, 0.0), 0.3, Conductor::silver(0.1)));
+
+    // glass blobs melting into a ring
+    let blob = SmoothUnion::new(
+        SmoothUnion::new(SdfSphere::new(Point3::new(0.0, 0.7, 0.0), 0.5), SdfSphere::new(Point3::new(0.5, 1.2, 0.2), 0.35), 0.3),
+        SdfTorus::new(Point3::new(0.0, 0.3, 0.0), 0.8, 0.2),
+        0.3,
+    );
+    let blob_box = Aabb::from_points(Point3::new(-1.1, 0.0, -1.1), Point3::new(1.1, 1.7, 1.1));
+    world.add(SdfObject::new(blob, blob_box, Dielectric::new(1.5)));
+
+    // a twisted column of rounded boxes
+    let column = Twist::new(SdfBox::new(Point3::new(0.0, 1.0, 0.0), Vec3::new(0.4, 1.0, 0.4), 0.08), 1.2);
+    let column_box = Aabb::from_points(Point3::new(1.6, 0.0, -0.6), Point3::new(2.8, 2.0, 0.6));
+    world.add(SdfObject::new(Translate::new(column, Vec3::new(2.2, 0.0, 0.0)), column_box, Conductor::gold(0.2)));
+
+    // rows of studs, repeated endlessly but only traced inside their box
+    let studs = Repeat::new(SdfSphere::new(Point3::new(0.0, 0.0, 0.0), 0.12), Vec3::new(0.4, 0.0, 0.4));
+    let studs_box = Aabb::from_points(Point3::new(-3.8, 0.0, 1.4), Point3::new(3.8, 0.12, 2.6));
+    world.add(SdfObject::new(studs, studs_box, Lambertian::new(Color::new(0.2, 0.5, 0.8))));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        35.0,
+        Point3::new(0.0, 3.0, 9.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn terrain(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    // a 16 bit grayscale png or a raw float grid if one's given, otherwise fractal hills
+    let min = Point3::new(-10.0, 0.0, -10.0);
+    let size = Vec3::new(20.0, 3.0, 20.0);
+    let ground = Lambertian::new(Color::new(0.45, 0.5, 0.3));
+    let field = match &options.heightfield {
+        Some(filename) if filename.ends_with(".png") => Heightfield::load_png(filename, min, size, ground),
+        Some(filename) => Heightfield::load_raw(filename, min, size, ground),
+        None => Ok(rolling_hills(512, min, size, ground)),
+    };
+    world.add(field.expect("Could not read heightfield."));
+
+    // a lake filling the valleys
+    world.add(Plane::new(Point3::new(0.0, 0.9, 0.0), Vec3::new(0.0, 1.0, 0.0), Dielectric::new(1.33)));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        40.0,
+        Point3::new(0.0, 6.0, 14.0),
+        Point3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn rolling_hills(n: usize, min: Point3, size: Vec3, mat: Lambertian) -> Heightfield {
+    // layers of noise, each at twice the frequency and half the height of the last
+    let noise = Perlin::new();
+    let mut heights = Vec::with_capacity(n * n);
+
+    for j in 0..n {
+        for i in 0..n {
+            let p = Point3::new(i as f64, 0.0, j as f64) / n as f64;
+            let mut height = 0.0;
+            let mut amplitude = 0.5;
+            let mut frequency = 3.0;
+            for _ in 0..6 {
+                height += amplitude * noise.noise(&(frequency * p));
+                amplitude *= 0.5;
+                frequency *= 2.0;
+            }
+            heights.push((0.4 + 1.5 * height).clamp(0.0, 1.0));
+        }
+    }
+
+    Heightfield::new(n, n, heights, min, size, mat)
+}
+
+fn subdivision(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    world.add(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+    if let Some(filename) = &options.mesh {
+        // a model's cage, smoothed with whichever scheme suits its faces and fitted onto the floor
+        let cage = fit_mesh(Mesh::load_obj(filename).expect("Could not read mesh."), Point3::new(0.0, 1.5, 0.0), 3.0);
+        let scheme = if cage.faces.iter().all(|face| face.len() == 3) {
+            SubdivisionScheme::Loop
+        } else {
+            SubdivisionScheme::CatmullClark
+        };
+        world.add(TriangleMesh::new(&fit_mesh(cage.clone(), Point3::new(-3.5, 1.5, 0.0), 3.0), Lambertian::new(Color::new(0.8, 0.8, 0.8))));
+        world.add(SubdivisionSurface::new(&cage, scheme, 3, Principled::new(Arc::new(SolidColor::new(Color::new(0.7, 0.3, 0.2))))));
+    } else {
+        // the same cube cage, sharp where its edges are creased
+        let clay = || Lambertian::new(Color::new(0.8, 0.5, 0.3));
+        world.add(SubdivisionSurface::new(&cube_cage(Point3::new(-3.3, 0.8, 0.0), 1.6), SubdivisionScheme::CatmullClark, 4, clay()));
+
+        let mut rounded = cube_cage(Point3::new(-1.1, 0.8, 0.0), 1.6);
+        for face in rounded.faces.clone() {
+            for k in 0..4 {
+                rounded.set_crease(face[k], face[(k + 1) % 4], 1.5);
+            }
+        }
+        world.add(SubdivisionSurface::new(&rounded, SubdivisionScheme::CatmullClark, 4, clay()));
+
+        let mut lidded = cube_cage(Point3::new(1.1, 0.8, 0.0), 1.6);
+        for (a, b) in [(2, 3), (3, 7), (7, 6), (6, 2)] {
+            lidded.set_crease(a, b, 10.0);
+        }
+        world.add(SubdivisionSurface::new(&lidded, SubdivisionScheme::CatmullClark, 4, Conductor::copper(0.2)));
+
+        // loop subdivision rounds an octahedron off into something like a sphere, with one edge
+        // kept sharp as a ridge
+        let mut octahedron = Mesh::new(
+            [(0.0, 1.0, 0.0), (0.0, -1.0, 0.0), (1.0, 0.0, 0.0), (0.0, 0.0, -1.0), (-1.0, 0.0, 0.0), (0.0, 0.0, 1.0)]
+                .iter()
+                .map(|&(x, y, z)| Point3::new(3.4 + 1.2 * x, 0.9 + 1.2 * y, 1.2 * z))
+                .collect(),
+            vec![vec![0, 2, 3], vec![0, 3, 4], vec![0, 4, 5], vec![0, 5, 2], vec![1, 3, 2], vec![1, 4, 3], vec![1, 5, 4], vec![1, 2, 5]],
+        );
+        octahedron.set_crease(0, 5, 10.0);
+        world.add(SubdivisionSurface::new(&octahedron, SubdivisionScheme::Loop, 4, Dielectric::new(1.5)));
+    }
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        35.0,
+        Point3::new(0.0, 4.0, 10.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn cube_cage(center: Point3, size: f64) -> Mesh {
+    // a cube's eight corners, numbered by which of x, y and z they're at the high end of
+    let h = 0.5 * size;
+    let positions = (0..8)
+        .map(|i| {
+            let offset = |bit: i32| if i & bit == 0 { -h } else { h };
+            center + Vec3::new(offset(1), offset(2), offset(4))
+        })
+        .collect();
+
+    let faces = vec![vec![0, 2, 3, 1], vec![4, 5, 7, 6], vec![0, 1, 5, 4], vec![2, 6, 7, 3], vec![0, 4, 6, 2], vec![1, 3, 7, 5]];
+    Mesh::new(positions, faces)
+}
+
+fn fit(positions: impl Iterator<Item = Point3>, size: f64) -> (Point3, f64) {
+    // the middle of the positions' bounding box, and the scale that makes it `size` across at its
+    // widest. a position p is fitted around a new center c as c + scale * (p - middle)
+    let bbox = positions.fold(aabb::EMPTY, |bbox, p| Aabb::surrounding(&bbox, &Aabb::from_points(p, p)));
+    let middle = Point3::new(
+        0.5 * (bbox.x.min + bbox.x.max),
+        0.5 * (bbox.y.min + bbox.y.max),
+        0.5 * (bbox.z.min + bbox.z.max),
+    );
+    let widest = (bbox.x.max - bbox.x.min).max(bbox.y.max - bbox.y.min).max(bbox.z.max - bbox.z.min);
+
+    (middle, size / widest)
+}
+
+fn fit_mesh(mut mesh: Mesh, center: Point3, size: f64) -> Mesh {
+    // scales and moves a mesh so that its bounding box is centered on `center` and `size` across
+    // at its widest
+    let (middle, scale) = fit(mesh.positions.iter().copied(), size);
+    for p in mesh.positions.iter_mut() {
+        *p = center + scale * (*p - middle);
+    }
+    mesh
+}
+
+fn displacement(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    world.add(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+    // a boulder ridged with marble veins, from a model if one's given
+    let boulder = match &options.mesh {
+        Some(filename) => fit_mesh(Mesh::load_obj(filename).expect("Could not read mesh."), Point3::new(-1.3, 1.2, 0.0), 2.0),
+        None => sphere_mesh(Point3::new(-1.3, 1.2, 0.0), 1.0, 24, 12),
+    };
+    let veins = Arc::new(NoiseTexture::new(4.0));
+    let stone = Lambertian::new(Color::new(0.6, 0.55, 0.5));
+    world.add(DisplacedMesh::new(&boulder, veins, 0.15, 0.02, stone).expect("Could not displace mesh."));
+
+    // a square of raised tiles, from a checkerboard laid over two triangles
+    let mut slab = Mesh::new(
+        vec![Point3::new(0.6, 0.0, -1.2), Point3::new(3.0, 0.0, -1.2), Point3::new(3.0, 0.0, 1.2), Point3::new(0.6, 0.0, 1.2)],
+        vec![vec![0, 3, 2, 1]],
+    );
+    slab.uvs = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+    let tiles = Arc::new(CheckerTexture::new(0.3, Arc::new(SolidColor::gray(0.0)), Arc::new(SolidColor::gray(1.0))));
+    world.add(DisplacedMesh::new(&slab, tiles, 0.15, 0.03, Conductor::copper(0.3)).expect("Could not displace mesh."));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        35.0,
+        Point3::new(0.0, 4.0, 8.0),
+        Point3::new(0.3, 0.7, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn sphere_mesh(center: Point3, radius: f64, segments: usize, rings: usize) -> Mesh {
+    // a sphere of quads between lines of latitude and longitude, closed with triangles around a
+    // vertex at each pole
+    let point = |phi: f64, theta: f64| center + radius * Vec3::new(theta.sin() * phi.cos(), -theta.cos(), -theta.sin() * phi.sin());
+
+    let mut positions = vec![point(0.0, 0.0)];
+    for j in 1..rings {
+        for i in 0..segments {
+            positions.push(point(2.0 * PI * i as f64 / segments as f64, PI * j as f64 / rings as f64));
+        }
+    }
+    positions.push(point(0.0, PI));
+
+    let top = positions.len() - 1;
+    let vertex = |i: usize, j: usize| 1 + (j - 1) * segments + i % segments;
+    let mut faces = Vec::new();
+    for i in 0..segments {
+        faces.push(vec![0, vertex(i + 1, 1), vertex(i, 1)]);
+        for j in 1..rings - 1 {
+            faces.push(vec![vertex(i, j), vertex(i + 1, j), vertex(i + 1, j + 1), vertex(i, j + 1)]);
+        }
+        faces.push(vec![vertex(i, rings - 1), vertex(i + 1, rings - 1), top]);
+    }
+
+    Mesh::new(positions, faces)
+}
+
+fn hair(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    world.add(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+
+    // two balls of fur, dark brown and dyed blue, drooping a little under their own weight
+    for (center, fur) in [
+        (Point3::new(-1.3, 1.0, 0.0), Hair::from_melanin(1.3, 0.6, 0.3, 0.3, 2.0)),
+        (Point3::new(1.3, 1.0, 0.0), Hair::new(Color::new(2.0, 0.8, 0.15), 0.25, 0.3, 2.0)),
+    ] {
+        world.add(Sphere::new(center, 0.6, Lambertian::new(Color::new(0.1, 0.08, 0.06))));
+
+        let mut strands = Vec::new();
+        for _ in 0..30000 {
+            let normal = sample_unit_vector((random_f64(), random_f64()));
+            let root = center + 0.6 * normal;
+            let length = 0.35 + 0.1 * random_f64();
+            let droop = Vec3::new(0.0, -0.4 * length, 0.0);
+            let points = [
+                root,
+                root + length / 3.0 * normal,
+                root + 2.0 * length / 3.0 * normal + 0.5 * droop,
+                root + length * normal + droop,
+            ];
+            strands.push(Curve::new(points, (0.006, 0.001)));
+        }
+        world.add(Curves::new(strands, CurveType::Flat, fur));
+    }
+
+    // blades of grass around them, bending away from the middle
+    let mut blades = Vec::new();
+    for _ in 0..8000 {
+        let root = Point3::new(random_range_f64(-3.0, 3.0), 0.0, random_range_f64(-1.5, 1.5));
+        let lean = 0.3 * unit_vector(Vec3::new(root.x(), 0.0, root.z()) + 0.5 * Vec3::random_range(-1.0, 1.0));
+        let height = 0.2 + 0.25 * random_f64();
+        let points = [
+            root,
+            root + Vec3::new(0.0, height / 3.0, 0.0),
+            root + Vec3::new(0.0, 2.0 * height / 3.0, 0.0) + 0.4 * height * lean,
+            root + Vec3::new(0.0, 0.9 * height, 0.0) + height * lean,
+        ];
+        blades.push(Curve::new(points, (0.02, 0.002)));
+    }
+    world.add(Curves::new(blades, CurveType::Flat, Lambertian::new(Color::new(0.15, 0.4, 0.08))));
+
+    // a copper wire coiled around the middle, shaded as round
+    let coil: Vec<Curve> = (0..24)
+        .map(|i| {
+            let point = |a: f64| Point3::new(0.25 * (a * 0.5).cos(), 0.1 + 0.03 * a, 0.25 * (a * 0.5).sin()) + Vec3::new(0.0, 0.0, 0.3);
+            let (a, b) = (i as f64, i as f64 + 1.0);
+            Curve::new([point(a), point(a + (b - a) / 3.0), point(a + 2.0 * (b - a) / 3.0), point(b)], (0.04, 0.04))
+        })
+        .collect();
+    world.add(Curves::new(coil, CurveType::Cylinder, Conductor::copper(0.2)));
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        35.0,
+        Point3::new(0.0, 2.5, 6.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn fit_points(mut points: Points, center: Point3, size: f64) -> Points {
+    // fit_mesh for a point cloud, scaling the points' radii along with it
+    let (middle, scale) = fit((0..points.positions.len()).map(|i| points.position(i)), size);
+    for i in 0..points.positions.len() {
+        let p = center + scale * (points.position(i) - middle);
+        points.positions[i] = [p.x() as f32, p.y() as f32, p.z() as f32];
+    }
+    for radius in points.radii.iter_mut() {
+        *radius *= scale as f32;
+    }
+    points.radius *= scale;
+    points
+}
+
+fn point_clouds(options: &Options) -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    if let Some(filename) = &options.points {
+        // a scan as splats, fitted into the middle of the scene. points without a size of their
+        // own are made big enough to close the gaps between them on a surface filling the box
+        let mut scan = fit_points(Points::load(filename, 1.0).expect("Could not read point cloud."), Point3::new(0.0, 1.0, 0.0), 5.0);
+        if scan.radii.is_empty() {
+            scan.radius = 10.0 / (scan.positions.len() as f64).sqrt();
+        }
+        world.add(PointCloud::new(scan, PointShape::Disc, Lambertian::new(Color::new(0.8, 0.8, 0.8))));
+    } else {
+        // a scanned hillside of splats, grassy in the hollows and rocky on the tops
+        let noise = Perlin::new();
+        let mut ground = Points::new(0.012);
+        for _ in 0..600_000 {
+            let (x, z) = (random_range_f64(-4.0, 4.0), random_range_f64(-4.0, 2.0));
+            let mut height = 0.0;
+            let mut amplitude = 0.5;
+            let mut frequency = 0.4;
+            for _ in 0..5 {
+                height += amplitude * noise.noise(&(frequency * Point3::new(x, 0.0, z)));
+                amplitude *= 0.5;
+                frequency *= 2.0;
+            }
+            let rock = (4.0 * height + 0.3).clamp(0.0, 1.0);
+            let shade = 0.85 + 0.3 * random_f64();
+            let color = [(70.0 + 70.0 * rock) * shade, (110.0 + 15.0 * rock) * shade, (40.0 + 70.0 * rock) * shade].map(|c| c as u8);
+            ground.push(Point3::new(x, 0.8 * height, z), color);
+        }
+        world.add(PointCloud::new(ground, PointShape::Disc, Lambertian::new(Color::new(0.8, 0.8, 0.8))));
+
+        // a torus of beads standing on it, colored by which way each faces
+        let mut beads = Points::new(0.016);
+        for _ in 0..20_000 {
+            let (a, b) = (2.0 * PI * random_f64(), 2.0 * PI * random_f64());
+            let normal = Vec3::new(b.cos() * a.cos(), b.sin(), b.cos() * a.sin());
+            let p = Point3::new(0.8 * a.cos(), 0.0, 0.8 * a.sin()) + 0.3 * normal;
+            let color = [normal.x(), normal.y(), normal.z()].map(|n| (127.5 + 127.5 * n) as u8);
+            beads.push(Point3::new(p.x(), 1.4 + p.z(), -p.y()), color);
+        }
+        world.add(PointCloud::new(beads, PointShape::Sphere, Lambertian::new(Color::new(0.8, 0.8, 0.8))));
+    }
+
+    let camera = Camera::new(
+        16.0 / 9.0,
+        options.image_width,
+        options.samples_per_pixel,
+        50,
+        40.0,
+        Point3::new(0.0, 2.5, 6.0),
+        Point3::new(0.0, 0.8, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        0.0,
+        10.0,
+    );
+
+    (world, camera)
+}
+
+fn main() {
+    let options = parse_args();
+
+    // the scene is built from random numbers too, and has to come out the same when resuming
+    seed_random(options.seed);
+
+    let (world, mut camera) = match options.scene.as_str() {
+        "spheres" => random_spheres(&options),
+        "metals" => metals(&options),
+        "glass" => glass(&options),
+        "principled" => principled(&options),
+        "layered" => layered(&options),
+        "bumps" => bumps(&options),
+        "cutouts" => cutouts(&options),
+        "fog" => fog(&options),
+        "smoke" => smoke(&options),
+        "subsurface" => subsurface(&options),
+        "prism" => prism(&options),
+        "shapes" => shapes(&options),
+        "cornell" => cornell_box(&options),
+        "pipes" => pipes(&options),
+        "csg" => csg(&options),
+        "sdf" => sdf(&options),
+        "terrain" => terrain(&options),
+        "subdivision" => subdivision(&options),
+        "displacement" => displacement(&options),
+        "hair" => hair(&options),
+        "points" => point_clouds(&options),
+        _ => {
+            eprintln!("Unknown scene: {}", options.scene);
+            process::exit(1);
+        }
+    };
+
+    camera.adaptive_threshold = options.adaptive_threshold;
+    camera.min_samples_per_pixel = options.min_samples_per_pixel;
+    camera.write_sample_map = options.write_sample_map;
+    camera.sampler = options.sampler;
+    camera.filter = options.filter;
+    camera.filter_radius = options.filter_radius;
+    camera.progressive_samples = options.progressive_samples;
+    camera.snapshot_interval = options.snapshot_interval;
+    camera.seed = options.seed;
+    camera.checkpoint_file = options.checkpoint_file;
+    camera.checkpoint_interval = options.checkpoint_interval;
+    camera.resume = options.resume;
+    camera.time_budget = options.time_budget;
+    camera.sample_budget = options.sample_budget;
+    camera.target_noise = options.target_noise;
+    camera.spectral = options.spectral;
+
+    if let Err(e) = camera.render(BvhNode::new(world)) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 }
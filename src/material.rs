@@ -1,7 +1,29 @@
-use crate::common::random_f64;
+use std::ops::Mul;
+use std::sync::Arc;
+
+use crate::common::PI;
 use crate::hittable::HitRecord;
+use crate::image::Image;
+use crate::microfacet::{fresnel_complex, fresnel_conductor, fresnel_dielectric, refract_microfacet, TrowbridgeReitz};
+use crate::onb::Onb;
 use crate::ray::Ray;
-use crate::vector::{dot, random_unit_vector, reflect, refract, unit_vector, Color};
+use crate::sampler::Sampler;
+use crate::spectrum::{uplift, Ior, SampledSpectrum, SampledWavelengths};
+use crate::texture::{SolidColor, Texture};
+use crate::vector::{cross, dot, luminance, reflect, refract, sample_unit_vector, unit_vector, Color, Point3, Vec3};
+
+// roughness of the principled material's clearcoat layer, as a GGX alpha
+const CLEARCOAT_ALPHA: f64 = 0.01;
+
+// most bounces followed inside a coated material's layer before the path is given up on
+const MAX_COAT_BOUNCES: i32 = 16;
+
+// step in texture coordinates for the finite differences of bump maps
+const BUMP_DELTA: f64 = 0.0005;
+
+// smallest cosine allowed between a tilted shading normal and the direction it must stay facing,
+// the viewer for normal and bump maps or the true surface normal for interpolated ones
+pub const MIN_SHADING_COS: f64 = 0.01;
 
 // pub enum Materials {
 //     Lambertian(Lambertian),
@@ -9,7 +31,33 @@ use crate::vector::{dot, random_unit_vector, reflect, refract, unit_vector, Colo
 // }
 
 pub trait Material {
-    fn scatter(&self, r_in: Ray, record: &HitRecord) -> Option<(Color, Ray)>;
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)>;
+
+    fn opacity(&self, _u: f64, _v: f64, _p: &Point3) -> f64 {
+        // fraction of rays the surface stops; the rest pass through as if it wasn't there
+        1.0
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        Color::new(0.0, 0.0, 0.0)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        // materials that don't vary with wavelength scatter as usual, with their color uplifted
+        // to a spectrum
+        let (attenuation, scattered) = self.scatter(r_in, record, sampler)?;
+        Some((uplift(attenuation, lambda), scattered))
+    }
+
+    fn emitted_spectral(&self, u: f64, v: f64, p: &Point3, lambda: &SampledWavelengths) -> SampledSpectrum {
+        uplift(self.emitted(u, v, p), lambda)
+    }
 }
 
 pub struct Lambertian {
@@ -23,8 +71,8 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r_in: Ray, record: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = record.normal + random_unit_vector();
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let mut scatter_direction = record.normal + sample_unit_vector(sampler.get_2d());
 
         // catch degenerate scatter direction
         if scatter_direction.near_zero() {
@@ -49,9 +97,9 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: Ray, record: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
         let mut reflected = reflect(r_in.direction(), record.normal);
-        reflected = unit_vector(reflected) + (self.fuzz * random_unit_vector());
+        reflected = unit_vector(reflected) + (self.fuzz * sample_unit_vector(sampler.get_2d()));
         let scattered = Ray::new(record.p, reflected);
 
         if dot(&scattered.direction(), &record.normal) > 0.0 {
@@ -82,7 +130,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: Ray, record: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
         let attenuation = Color::new(1.0, 1.0, 1.0);
         let ri = if record.front_face { 1.0 / self.refraction_index } else { self.refraction_index };
 
@@ -97,7 +145,7 @@ impl Material for Dielectric {
 
         let cannot_refract = ri * sin_theta > 1.0;
 
-        let direction = if cannot_refract || Self::reflectance(cos_theta, ri) > random_f64() {
+        let direction = if cannot_refract || Self::reflectance(cos_theta, ri) > sampler.get_1d() {
             reflect(unit_direction, record.normal)
         } else {
             refract(unit_direction, record.normal, ri)
@@ -107,4 +155,887 @@ impl Material for Dielectric {
 
         return Some((attenuation, scattered));
     }
-}
\ No newline at end of file
+}
+
+// rough metal modelled as a GGX microfacet surface. eta and k are the real and imaginary parts
+// of the metal's index of refraction, per color channel
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness_u: f64, roughness_v: f64) -> Self {
+        let distribution = TrowbridgeReitz::new(
+            TrowbridgeReitz::roughness_to_alpha(roughness_u),
+            TrowbridgeReitz::roughness_to_alpha(roughness_v),
+        );
+
+        Self { eta, k, distribution }
+    }
+
+    // measured indices of refraction at roughly 650, 550 and 450 nm
+    pub fn gold(roughness: f64) -> Self {
+        Self::new(Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603), roughness, roughness)
+    }
+
+    pub fn copper(roughness: f64) -> Self {
+        Self::new(Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142), roughness, roughness)
+    }
+
+    pub fn aluminium(roughness: f64) -> Self {
+        Self::new(Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.270, 4.837), roughness, roughness)
+    }
+
+    pub fn silver(roughness: f64) -> Self {
+        Self::new(Color::new(0.155, 0.117, 0.138), Color::new(4.828, 3.122, 2.147), roughness, roughness)
+    }
+
+    fn sample_reflection(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(f64, f64, Ray)> {
+        // reflect about a visible microfacet normal. with that sampling strategy the BRDF, cosine
+        // and pdf reduce to Fresnel times the ratio of masking-shadowing to masking. returns the
+        // cosine for Fresnel along with that ratio. the frame's x axis runs along dpdu, so
+        // roughness_u is the roughness in the direction u increases
+        let frame = Onb::from_tangent(record.normal, record.dpdu);
+        let wo = frame.to_local(-unit_vector(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let wm = self.distribution.sample_wm(wo, sampler.get_2d());
+        let wi = reflect(-wo, wm);
+        if wi.z() <= 0.0 {
+            return None;
+        }
+
+        let weight = self.distribution.g(wo, wi) / self.distribution.g1(wo);
+        Some((dot(&wo, &wm), weight, Ray::new(record.p, frame.to_world(wi))))
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let (cos_theta, weight, scattered) = self.sample_reflection(r_in, record, sampler)?;
+        Some((weight * fresnel_conductor(cos_theta, self.eta, self.k), scattered))
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        // the index of refraction is interpolated between the three measured wavelengths, which
+        // keeps the color shift of metals at grazing angles
+        let (cos_theta, weight, scattered) = self.sample_reflection(r_in, record, sampler)?;
+
+        let mut attenuation = SampledSpectrum::constant(weight);
+        for (i, value) in attenuation.values.iter_mut().enumerate() {
+            let l = lambda.get(i);
+            *value *= fresnel_complex(cos_theta, measured_at(self.eta, l), measured_at(self.k, l));
+        }
+
+        Some((attenuation, scattered))
+    }
+}
+
+// frosted glass: a GGX microfacet interface that both reflects and transmits, with Beer-Lambert
+// absorption inside. `tint` is the color of light after travelling a unit distance through the
+// material, so white is perfectly clear glass
+pub struct RoughDielectric {
+    refraction_index: f64,
+    distribution: TrowbridgeReitz,
+    absorption: Color,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64, tint: Color) -> Self {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        // a channel of 0 would absorb infinitely, which turns into NaN over a distance of 0, so
+        // the darkest tint is merely very strongly absorbing
+        let absorption = |channel: f64| -channel.max(1e-6).ln();
+        let absorption = Color::new(absorption(tint.x()), absorption(tint.y()), absorption(tint.z()));
+
+        Self { refraction_index, distribution: TrowbridgeReitz::new(alpha, alpha), absorption }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let frame = Onb::new(record.normal);
+        let wo = frame.to_local(-unit_vector(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let eta = if record.front_face { self.refraction_index } else { 1.0 / self.refraction_index };
+        let (weight, wi) = sample_interface(&self.distribution, wo, eta, sampler)?;
+        let mut attenuation = weight * Color::new(1.0, 1.0, 1.0);
+
+        // a ray hitting the back face has just travelled through the inside
+        if !record.front_face {
+            let distance = record.t * r_in.direction().length();
+            attenuation = attenuation * Color::new(
+                (-self.absorption.x() * distance).exp(),
+                (-self.absorption.y() * distance).exp(),
+                (-self.absorption.z() * distance).exp(),
+            );
+        }
+
+        Some((attenuation, Ray::new(record.p, frame.to_world(wi))))
+    }
+}
+
+// clear or frosted glass whose index of refraction varies with wavelength, so that in spectral
+// mode it splits white light into its colors. rendering in RGB uses the index at 587.6 nm, the
+// wavelength glass is usually specified at
+pub struct DispersiveDielectric {
+    ior: Ior,
+    distribution: TrowbridgeReitz,
+}
+
+impl DispersiveDielectric {
+    pub fn new(ior: Ior, roughness: f64) -> Self {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        Self { ior, distribution: TrowbridgeReitz::new(alpha, alpha) }
+    }
+
+    fn sample(&self, r_in: Ray, record: &HitRecord, refraction_index: f64, sampler: &mut dyn Sampler) -> Option<(f64, Ray)> {
+        let frame = Onb::new(record.normal);
+        let wo = frame.to_local(-unit_vector(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let eta = if record.front_face { refraction_index } else { 1.0 / refraction_index };
+        let (weight, wi) = sample_interface(&self.distribution, wo, eta, sampler)?;
+        Some((weight, Ray::new(record.p, frame.to_world(wi))))
+    }
+}
+
+impl Material for DispersiveDielectric {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let (weight, scattered) = self.sample(r_in, record, self.ior.at(587.6), sampler)?;
+        Some((weight * Color::new(1.0, 1.0, 1.0), scattered))
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        // each wavelength would bend its own way, so only the hero wavelength carries on
+        if self.ior.is_dispersive() {
+            lambda.terminate_secondary();
+        }
+
+        let (weight, scattered) = self.sample(r_in, record, self.ior.at(lambda.hero()), sampler)?;
+        Some((SampledSpectrum::constant(weight), scattered))
+    }
+}
+
+// a surface that only gives off light
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: Ray, _record: &HitRecord, _sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.emit
+    }
+}
+
+// Disney / glTF style "principled" material covering plastics, metals, glass and everything in
+// between with one set of parameters. every parameter is a texture; scalar parameters are read
+// from the texture's first channel
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    pub specular: Arc<dyn Texture>, // at 0.5 dielectric reflection is exactly what the IOR predicts
+    pub specular_tint: Arc<dyn Texture>, // how much dielectric reflection takes on the base color
+    pub sheen: Arc<dyn Texture>, // extra grazing angle reflection, as on cloth
+    pub clearcoat: Arc<dyn Texture>, // strength of a glossy varnish layer on top
+    pub transmission: Arc<dyn Texture>, // how much light goes through rather than diffusing
+    pub ior: Arc<dyn Texture>,
+}
+
+impl Principled {
+    pub fn new(base_color: Arc<dyn Texture>) -> Self {
+        let constant = |x: f64| -> Arc<dyn Texture> { Arc::new(SolidColor::gray(x)) };
+
+        Self {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            transmission: constant(0.0),
+            ior: constant(1.5),
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let (u, v, p) = (record.u, record.v, &record.p);
+        let scalar = |texture: &Arc<dyn Texture>| texture.value(u, v, p).x().clamp(0.0, 1.0);
+
+        let base_color = self.base_color.value(u, v, p);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let specular = scalar(&self.specular);
+        let specular_tint = scalar(&self.specular_tint);
+        let sheen = scalar(&self.sheen);
+        let clearcoat = scalar(&self.clearcoat);
+        let transmission = scalar(&self.transmission);
+        let ior = self.ior.value(u, v, p).x().max(1.0);
+
+        let frame = Onb::new(record.normal);
+        let wo = frame.to_local(-unit_vector(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        let distribution = TrowbridgeReitz::new(alpha, alpha);
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        // hue of the base color at full brightness, for the tint parameters
+        let tint_color = if luminance(base_color) > 0.0 { base_color / luminance(base_color) } else { white };
+
+        // the only way to be inside is through transmission, so only the rough interface applies.
+        // without any, a back face is just the other side of an open or one-sided surface, and is
+        // shaded like the front, with the normal already turned to face the ray
+        if !record.front_face && transmission > 0.0 && metallic < 1.0 {
+            let wm = distribution.sample_wm(wo, sampler.get_2d());
+            let eta = 1.0 / ior;
+            let reflectance = fresnel_dielectric(dot(&wo, &wm), eta);
+
+            return match refract_microfacet(wo, wm, eta) {
+                Some(wi) if sampler.get_1d() >= reflectance => {
+                    if wi.z() >= 0.0 {
+                        return None;
+                    }
+                    let attenuation = distribution.g(wo, wi) / distribution.g1(wo) * white;
+                    Some((attenuation, Ray::new(record.p, frame.to_world(wi))))
+                }
+                _ => microfacet_reflection(&distribution, wo, wm, white, &frame, record.p),
+            };
+        }
+
+        // each layer is picked with the probability that light interacts with it, from the top
+        // down: clearcoat, then metal or dielectric specular, then transmission or diffuse
+        if clearcoat > 0.0 {
+            let coat = TrowbridgeReitz::new(CLEARCOAT_ALPHA, CLEARCOAT_ALPHA);
+            let wm = coat.sample_wm(wo, sampler.get_2d());
+            if sampler.get_1d() < clearcoat * fresnel_dielectric(dot(&wo, &wm), 1.5) {
+                return microfacet_reflection(&coat, wo, wm, white, &frame, record.p);
+            }
+        }
+
+        let wm = distribution.sample_wm(wo, sampler.get_2d());
+        let cos_theta = dot(&wo, &wm);
+
+        if sampler.get_1d() < metallic {
+            let fresnel = base_color + (white - base_color) * schlick_weight(cos_theta);
+            return microfacet_reflection(&distribution, wo, wm, fresnel, &frame, record.p);
+        }
+
+        let reflectance = (2.0 * specular * fresnel_dielectric(cos_theta, ior)).min(1.0);
+        if sampler.get_1d() < reflectance {
+            let specular_color = (1.0 - specular_tint) * white + specular_tint * tint_color;
+            return microfacet_reflection(&distribution, wo, wm, specular_color, &frame, record.p);
+        }
+
+        if sampler.get_1d() < transmission {
+            return match refract_microfacet(wo, wm, ior) {
+                Some(wi) if wi.z() < 0.0 => {
+                    let attenuation = distribution.g(wo, wi) / distribution.g1(wo) * base_color;
+                    Some((attenuation, Ray::new(record.p, frame.to_world(wi))))
+                }
+                _ => None,
+            };
+        }
+
+        // cosine weighted diffuse bounce, with the sheen lobe added at grazing angles
+        let mut wi = Vec3::new(0.0, 0.0, 1.0) + sample_unit_vector(sampler.get_2d());
+        if wi.near_zero() {
+            wi = Vec3::new(0.0, 0.0, 1.0);
+        }
+        let wi = unit_vector(wi);
+
+        let cos_theta_d = dot(&wi, &unit_vector(wi + wo));
+        let sheen_color = 0.5 * (white + tint_color);
+        let attenuation = base_color + PI * sheen * schlick_weight(cos_theta_d) * sheen_color;
+
+        Some((attenuation, Ray::new(record.p, frame.to_world(wi))))
+    }
+}
+
+// stochastic blend of two materials: each scattering event picks the second material with
+// probability `amount`, which can vary over the surface. use `SolidColor::gray` for a constant blend
+pub struct MixMaterial {
+    first: Box<dyn Material>,
+    second: Box<dyn Material>,
+    amount: Arc<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(first: impl Material + 'static, second: impl Material + 'static, amount: Arc<dyn Texture>) -> Self {
+        Self { first: Box::new(first), second: Box::new(second), amount }
+    }
+}
+
+impl Material for MixMaterial {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let amount = self.amount.value(record.u, record.v, &record.p).x().clamp(0.0, 1.0);
+
+        if sampler.get_1d() < amount {
+            self.second.scatter(r_in, record, sampler)
+        } else {
+            self.first.scatter(r_in, record, sampler)
+        }
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        let amount = self.amount.value(u, v, p).x().clamp(0.0, 1.0);
+        (1.0 - amount) * self.first.opacity(u, v, p) + amount * self.second.opacity(u, v, p)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let amount = self.amount.value(u, v, p).x().clamp(0.0, 1.0);
+        (1.0 - amount) * self.first.emitted(u, v, p) + amount * self.second.emitted(u, v, p)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let amount = self.amount.value(record.u, record.v, &record.p).x().clamp(0.0, 1.0);
+
+        if sampler.get_1d() < amount {
+            self.second.scatter_spectral(r_in, record, lambda, sampler)
+        } else {
+            self.first.scatter_spectral(r_in, record, lambda, sampler)
+        }
+    }
+
+    fn emitted_spectral(&self, u: f64, v: f64, p: &Point3, lambda: &SampledWavelengths) -> SampledSpectrum {
+        let amount = self.amount.value(u, v, p).x().clamp(0.0, 1.0);
+        SampledSpectrum::constant(1.0 - amount) * self.first.emitted_spectral(u, v, p, lambda)
+            + SampledSpectrum::constant(amount) * self.second.emitted_spectral(u, v, p, lambda)
+    }
+}
+
+// a thin clear or tinted dielectric coat, such as varnish or lacquer, over any other material.
+// light is followed through the layer: reflected or refracted at the coat, scattered by the base,
+// then reflected back down or let out by the underside of the coat until it leaves. `tint` is the
+// color of light after passing straight through the coat once
+pub struct Coated {
+    base: Box<dyn Material>,
+    refraction_index: f64,
+    distribution: TrowbridgeReitz,
+    tint: Color,
+}
+
+impl Coated {
+    pub fn new(base: impl Material + 'static, refraction_index: f64, roughness: f64, tint: Color) -> Self {
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+        Self { base: Box::new(base), refraction_index, distribution: TrowbridgeReitz::new(alpha, alpha), tint }
+    }
+
+    fn transmittance(&self, w: Vec3) -> Color {
+        // slanted paths through the coat are longer than the straight one the tint is given for
+        let exponent = 1.0 / w.z().abs().max(1e-4);
+        Color::new(self.tint.x().powf(exponent), self.tint.y().powf(exponent), self.tint.z().powf(exponent))
+    }
+
+    fn scatter_through_coat<T: Mul<Output = T>>(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        sampler: &mut dyn Sampler,
+        mut scatter_base: impl FnMut(Ray, &mut dyn Sampler) -> Option<(T, Ray)>,
+    ) -> Option<(Color, Option<T>, Ray)> {
+        // the walk through the layer, shared by RGB and spectral rendering. what the coat does is
+        // gathered as a color, and what the base does in whatever `scatter_base` returns, which is
+        // None if light never reached the base
+        let frame = Onb::new(record.normal);
+        let wo = frame.to_local(-unit_vector(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        let (weight, mut wi) = sample_interface(&self.distribution, wo, self.refraction_index, sampler)?;
+        let mut attenuation = weight * Color::new(1.0, 1.0, 1.0);
+        let mut base_attenuation: Option<T> = None;
+
+        // seen from below the coat's normal points down, which the interface sampling expects as +z
+        let flip = |w: Vec3| Vec3::new(w.x(), w.y(), -w.z());
+
+        for _ in 0..MAX_COAT_BOUNCES {
+            if wi.z() > 0.0 {
+                return Some((attenuation, base_attenuation, Ray::new(record.p, frame.to_world(wi))));
+            }
+            attenuation = attenuation * self.transmittance(wi);
+
+            let (color, scattered) = scatter_base(Ray::new(record.p, frame.to_world(wi)), sampler)?;
+            base_attenuation = Some(match base_attenuation {
+                Some(base) => base * color,
+                None => color,
+            });
+
+            // light the base lets into the object doesn't come back through the coat
+            let up = frame.to_local(unit_vector(scattered.direction()));
+            if up.z() <= 0.0 {
+                return Some((attenuation, base_attenuation, scattered));
+            }
+            attenuation = attenuation * self.transmittance(up);
+
+            let (weight, flipped) = sample_interface(&self.distribution, flip(-up), 1.0 / self.refraction_index, sampler)?;
+            attenuation = attenuation * weight;
+            wi = flip(flipped);
+        }
+
+        None
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        // the coat is only on the outside; from within the object only the base is seen
+        if !record.front_face {
+            return self.base.scatter(r_in, record, sampler);
+        }
+
+        let (attenuation, base, scattered) =
+            self.scatter_through_coat(r_in, record, sampler, |r, sampler| self.base.scatter(r, record, sampler))?;
+        Some((base.map_or(attenuation, |base| attenuation * base), scattered))
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.base.opacity(u, v, p)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(u, v, p)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        if !record.front_face {
+            return self.base.scatter_spectral(r_in, record, lambda, sampler);
+        }
+
+        let (attenuation, base, scattered) = self.scatter_through_coat(r_in, record, sampler, |r, sampler| {
+            self.base.scatter_spectral(r, record, lambda, sampler)
+        })?;
+        let attenuation = uplift(attenuation, lambda);
+        Some((base.map_or(attenuation, |base| attenuation * base), scattered))
+    }
+
+    fn emitted_spectral(&self, u: f64, v: f64, p: &Point3, lambda: &SampledWavelengths) -> SampledSpectrum {
+        self.base.emitted_spectral(u, v, p, lambda)
+    }
+}
+
+// tilts the shading normal of another material with a tangent space normal map, as exported by
+// most texturing tools: red runs along u, green along v and blue away from the surface. the map
+// holds directions rather than colors, so it is read without any sRGB decoding
+pub struct NormalMap {
+    base: Box<dyn Material>,
+    image: Image,
+}
+
+impl NormalMap {
+    pub fn new(base: impl Material + 'static, filename: &str) -> Self {
+        let image = Image::load_png(filename).expect("Could not read normal map.");
+        Self { base: Box::new(base), image }
+    }
+}
+
+impl NormalMap {
+    fn normal(&self, record: &HitRecord) -> Vec3 {
+        let x = record.u * self.image.width() as f64;
+        let y = (1.0 - record.v) * self.image.height() as f64;
+        let local = 2.0 * self.image.bilinear(x, y) - Color::new(1.0, 1.0, 1.0);
+
+        // tangent frame around the outward surface normal, with the tangent along u
+        let n = if record.front_face { record.geometric_normal } else { -record.geometric_normal };
+        let tangent = unit_vector(record.dpdu - dot(&record.dpdu, &n) * n);
+        let mut bitangent = cross(n, tangent);
+        if dot(&bitangent, &record.dpdv) < 0.0 {
+            bitangent = -bitangent;
+        }
+
+        unit_vector(local.x() * tangent + local.y() * bitangent + local.z() * n)
+    }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let shading = shading_record(self.normal(record), &r_in, record);
+        let (attenuation, scattered) = self.base.scatter(r_in, &shading, sampler)?;
+        keeps_side(record, &shading, &scattered).then_some((attenuation, scattered))
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let shading = shading_record(self.normal(record), &r_in, record);
+        let (attenuation, scattered) = self.base.scatter_spectral(r_in, &shading, lambda, sampler)?;
+        keeps_side(record, &shading, &scattered).then_some((attenuation, scattered))
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.base.opacity(u, v, p)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(u, v, p)
+    }
+
+    fn emitted_spectral(&self, u: f64, v: f64, p: &Point3, lambda: &SampledWavelengths) -> SampledSpectrum {
+        self.base.emitted_spectral(u, v, p, lambda)
+    }
+}
+
+// tilts the shading normal of another material as though the surface were raised by `height`,
+// which is read from the texture's first channel and multiplied by `scale` in world units
+pub struct BumpMap {
+    base: Box<dyn Material>,
+    height: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(base: impl Material + 'static, height: Arc<dyn Texture>, scale: f64) -> Self {
+        Self { base: Box::new(base), height, scale }
+    }
+}
+
+impl BumpMap {
+    fn normal(&self, record: &HitRecord) -> Vec3 {
+        let displacement = |u: f64, v: f64, p: Point3| self.scale * self.height.value(u, v, &p).x();
+        let (u, v, p) = (record.u, record.v, record.p);
+        let n = if record.front_face { record.geometric_normal } else { -record.geometric_normal };
+
+        // finite differences of the displaced surface along u and v
+        let displace = displacement(u, v, p);
+        let u_displace = displacement(u + BUMP_DELTA, v, p + BUMP_DELTA * record.dpdu);
+        let v_displace = displacement(u, v + BUMP_DELTA, p + BUMP_DELTA * record.dpdv);
+
+        let dpdu = record.dpdu + (u_displace - displace) / BUMP_DELTA * n;
+        let dpdv = record.dpdv + (v_displace - displace) / BUMP_DELTA * n;
+
+        let mut normal = unit_vector(cross(dpdu, dpdv));
+        if dot(&normal, &n) < 0.0 {
+            normal = -normal;
+        }
+        normal
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let shading = shading_record(self.normal(record), &r_in, record);
+        let (attenuation, scattered) = self.base.scatter(r_in, &shading, sampler)?;
+        keeps_side(record, &shading, &scattered).then_some((attenuation, scattered))
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let shading = shading_record(self.normal(record), &r_in, record);
+        let (attenuation, scattered) = self.base.scatter_spectral(r_in, &shading, lambda, sampler)?;
+        keeps_side(record, &shading, &scattered).then_some((attenuation, scattered))
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.base.opacity(u, v, p)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(u, v, p)
+    }
+
+    fn emitted_spectral(&self, u: f64, v: f64, p: &Point3, lambda: &SampledWavelengths) -> SampledSpectrum {
+        self.base.emitted_spectral(u, v, p, lambda)
+    }
+}
+
+// cuts holes in another material for leaves, fences and decals. `opacity` is read from the
+// texture's first channel: 0 lets rays through, 1 stops them, and values in between stop that
+// fraction of rays at random
+pub struct Cutout {
+    base: Box<dyn Material>,
+    opacity: Arc<dyn Texture>,
+}
+
+impl Cutout {
+    pub fn new(base: impl Material + 'static, opacity: Arc<dyn Texture>) -> Self {
+        Self { base: Box::new(base), opacity }
+    }
+}
+
+impl Material for Cutout {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        self.base.scatter(r_in, record, sampler)
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        self.base.scatter_spectral(r_in, record, lambda, sampler)
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.opacity.value(u, v, p).x().clamp(0.0, 1.0) * self.base.opacity(u, v, p)
+    }
+
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.base.emitted(u, v, p)
+    }
+
+    fn emitted_spectral(&self, u: f64, v: f64, p: &Point3, lambda: &SampledWavelengths) -> SampledSpectrum {
+        self.base.emitted_spectral(u, v, p, lambda)
+    }
+}
+
+// phase function of a participating medium that scatters light equally in every direction
+pub struct Isotropic {
+    albedo: Color,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        Some((self.albedo, Ray::new(record.p, sample_unit_vector(sampler.get_2d()))))
+    }
+}
+
+// Henyey-Greenstein phase function: g above 0 favours scattering forwards, as in fog and clouds,
+// and below 0 backwards. g of 0 is isotropic
+pub struct HenyeyGreenstein {
+    albedo: Color,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Color, g: f64) -> Self {
+        Self { albedo, g: g.clamp(-0.99, 0.99) }
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        // sample the angle from the direction of travel by inverting the phase function's CDF, so
+        // the albedo is the whole weight
+        let (u0, u1) = sampler.get_2d();
+        let g = self.g;
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u0
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u0);
+            ((1.0 + g * g - s * s) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u1;
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let direction = Onb::new(r_in.direction()).to_world(local);
+
+        Some((self.albedo, Ray::new(record.p, direction)))
+    }
+}
+
+fn shading_record<'a>(outward_normal: Vec3, r_in: &Ray, record: &HitRecord<'a>) -> HitRecord<'a> {
+    // the hit with its shading normal tilted by a normal or bump map
+    let mut shading = record.clone();
+    shading.set_shading_normal(&outward_normal);
+
+    // a shading normal tilted away from the viewer leaves nothing to shade, so tilt it back just
+    // far enough to face the incoming ray
+    let wo = -unit_vector(r_in.direction());
+    let cos_theta = dot(&wo, &shading.normal);
+    if cos_theta < MIN_SHADING_COS {
+        shading.normal = unit_vector(shading.normal + (MIN_SHADING_COS - cos_theta) * wo);
+    }
+
+    shading
+}
+
+fn keeps_side(record: &HitRecord, shading: &HitRecord, scattered: &Ray) -> bool {
+    // a direction on different sides of the shading and true surfaces would leak light through the
+    // surface, or reflect it into it
+    let direction = scattered.direction();
+    dot(&direction, &record.geometric_normal) * dot(&direction, &shading.normal) > 0.0
+}
+
+pub fn sample_interface(distribution: &TrowbridgeReitz, wo: Vec3, eta: f64, sampler: &mut dyn Sampler) -> Option<(f64, Vec3)> {
+    // picks reflection or transmission through a visible microfacet of a rough dielectric
+    // interface in proportion to its Fresnel reflectance, which leaves only the masking-shadowing
+    // ratio as the weight. wo and the result are in the local shading frame
+    let wm = distribution.sample_wm(wo, sampler.get_2d());
+    let reflectance = fresnel_dielectric(dot(&wo, &wm), eta);
+    let u = sampler.get_1d();
+
+    let wi = match refract_microfacet(wo, wm, eta) {
+        Some(refracted) if u >= reflectance => {
+            if refracted.z() >= 0.0 {
+                return None;
+            }
+            refracted
+        }
+        _ => {
+            let reflected = reflect(-wo, wm);
+            if reflected.z() <= 0.0 {
+                return None;
+            }
+            reflected
+        }
+    };
+
+    Some((distribution.g(wo, wi) / distribution.g1(wo), wi))
+}
+
+fn microfacet_reflection(
+    distribution: &TrowbridgeReitz,
+    wo: Vec3,
+    wm: Vec3,
+    color: Color,
+    frame: &Onb,
+    p: Point3,
+) -> Option<(Color, Ray)> {
+    // mirror wo about a sampled visible microfacet normal, weighted for that sampling strategy
+    let wi = reflect(-wo, wm);
+    if wi.z() <= 0.0 {
+        return None;
+    }
+
+    let attenuation = distribution.g(wo, wi) / distribution.g1(wo) * color;
+    Some((attenuation, Ray::new(p, frame.to_world(wi))))
+}
+
+fn measured_at(c: Color, lambda: f64) -> f64 {
+    // linear interpolation of a quantity measured at 650, 550 and 450 nm, held constant beyond
+    let t = ((lambda - 450.0) / 100.0).clamp(0.0, 2.0);
+    if t < 1.0 {
+        c.z() + t * (c.y() - c.z())
+    } else {
+        c.y() + (t - 1.0) * (c.x() - c.y())
+    }
+}
+
+fn schlick_weight(cos_theta: f64) -> f64 {
+    (1.0 - cos_theta).clamp(0.0, 1.0).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::seed_random;
+    use crate::sampler::SamplerType;
+
+    fn spread(mat: &dyn Material, dpdu: Vec3) -> (f64, f64) {
+        // mean distance of reflections from the mirror direction along z and along y, for a
+        // surface facing +x. a normal along x is where Onb::new swaps its helper axis
+        seed_random(5);
+        let mut sampler = SamplerType::Independent.create(1);
+        let mut record = HitRecord::new();
+        record.normal = Vec3::new(1.0, 0.0, 0.0);
+        record.geometric_normal = record.normal;
+        record.front_face = true;
+        record.dpdu = dpdu;
+
+        let (mut along_z, mut along_y, mut count) = (0.0, 0.0, 0);
+        for s in 0..4000 {
+            sampler.start_pixel_sample(0, 0, s);
+            let r_in = Ray::new(Point3::new(1.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+            if let Some((_, scattered)) = mat.scatter(r_in, &record, sampler.as_mut()) {
+                let wi = unit_vector(scattered.direction());
+                along_z += wi.z().abs();
+                along_y += wi.y().abs();
+                count += 1;
+            }
+        }
+
+        (along_z / count as f64, along_y / count as f64)
+    }
+
+    #[test]
+    fn anisotropic_highlight_follows_dpdu() {
+        // smooth along u and rough along v, so reflections spread across u rather than along it
+        let mat = Conductor::new(Color::new(0.2, 0.2, 0.2), Color::new(3.0, 3.0, 3.0), 0.05, 0.6);
+
+        let (along_z, along_y) = spread(&mat, Vec3::new(0.0, 0.0, 2.0));
+        assert!(along_z < 0.3 * along_y, "u along z: spread {along_z} along z, {along_y} along y");
+
+        // turning the parameterization a quarter turn turns the highlight with it
+        let (along_z, along_y) = spread(&mat, Vec3::new(0.0, 3.0, 0.0));
+        assert!(along_y < 0.3 * along_z, "u along y: spread {along_z} along z, {along_y} along y");
+    }
+
+    #[test]
+    fn opaque_principled_back_face_is_not_glass() {
+        // the back of a one-sided surface, such as a quad seen from behind, has nothing inside
+        // to refract into, so every bounce stays on the ray's side
+        seed_random(6);
+        let mut sampler = SamplerType::Independent.create(1);
+        let mat = Principled::new(Arc::new(SolidColor::new(Color::new(0.8, 0.3, 0.2))));
+        let mut record = HitRecord::new();
+        record.normal = Vec3::new(0.0, 1.0, 0.0);
+        record.geometric_normal = record.normal;
+        record.front_face = false;
+
+        for s in 0..1000 {
+            sampler.start_pixel_sample(0, 0, s);
+            let r_in = Ray::new(Point3::new(1.0, 1.0, 0.0), Vec3::new(-1.0, -1.0, 0.0));
+            if let Some((_, scattered)) = mat.scatter(r_in, &record, sampler.as_mut()) {
+                assert!(scattered.direction().y() > 0.0, "sample {s} went through the surface");
+            }
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::rc::Rc;
+
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle::{intersect_triangle, triangle_tangents};
+use crate::vector::{cross, unit_vector, Point3, Vec3};
+
+// a polygon mesh: faces are lists of indices into the vertices, wound counterclockwise seen from
+// outside. texture coordinates, if there are any, are one per vertex, so a mesh can't have uv
+// seams. creases mark edges with a sharpness, which subdivision keeps from being smoothed over for
+// that many levels; shading keeps an edge hard while its sharpness is at least 1
+#[derive(Clone)]
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub uvs: Vec<(f64, f64)>,
+    pub faces: Vec<Vec<usize>>,
+    pub creases: HashMap<(usize, usize), f64>,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Point3>, faces: Vec<Vec<usize>>) -> Self {
+        Self { positions, uvs: Vec::new(), faces, creases: HashMap::new() }
+    }
+
+    pub fn load_obj(filename: &str) -> io::Result<Self> {
+        // vertices, texture coordinates and faces from a wavefront obj file, plus creases as
+        // `t crease 2/1/0 <a> <b> <sharpness>` tags with zero-based vertex indices, the way
+        // opensubdiv writes them. normals, groups and materials are ignored
+        let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad obj line: {line}"));
+        let r = BufReader::new(File::open(filename)?);
+
+        let mut mesh = Self::new(Vec::new(), Vec::new());
+        let mut texture_coordinates = Vec::new();
+        let mut vertex_uvs = HashMap::new();
+
+        for line in r.lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let numbers = |fields: &[&str]| -> io::Result<Vec<f64>> {
+                fields.iter().map(|x| x.parse().map_err(|_| invalid(&line))).collect()
+            };
+
+            match fields.first().copied() {
+                Some("v") => {
+                    let xyz = numbers(&fields[1..])?;
+                    if xyz.len() < 3 {
+                        return Err(invalid(&line));
+                    }
+                    mesh.positions.push(Point3::new(xyz[0], xyz[1], xyz[2]));
+                }
+                Some("vt") => {
+                    let uv = numbers(&fields[1..])?;
+                    if uv.len() < 2 {
+                        return Err(invalid(&line));
+                    }
+                    texture_coordinates.push((uv[0], uv[1]));
+                }
+                Some("f") => {
+                    // indices count from one, or back from the end if they're negative
+                    let resolve = |index: &str, count: usize| -> Option<usize> {
+                        let index: i64 = index.parse().ok()?;
+                        let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+                        (0..count as i64).contains(&resolved).then_some(resolved as usize)
+                    };
+
+                    let mut face = Vec::new();
+                    for corner in &fields[1..] {
+                        let mut indices = corner.split('/');
+                        let vertex = resolve(indices.next().unwrap_or(""), mesh.positions.len()).ok_or_else(|| invalid(&line))?;
+                        if let Some(uv) = indices.next().and_then(|index| resolve(index, texture_coordinates.len())) {
+                            vertex_uvs.entry(vertex).or_insert(texture_coordinates[uv]);
+                        }
+                        face.push(vertex);
+                    }
+                    if face.len() < 3 {
+                        return Err(invalid(&line));
+                    }
+                    mesh.faces.push(face);
+                }
+                Some("t") if fields.get(1) == Some(&"crease") => {
+                    let values = numbers(fields.get(3..).unwrap_or(&[]))?;
+                    if values.len() < 3 || values[0] < 0.0 || values[1] < 0.0 {
+                        return Err(invalid(&line));
+                    }
+                    mesh.set_crease(values[0] as usize, values[1] as usize, values[2]);
+                }
+                _ => (),
+            }
+        }
+
+        // obj files give texture coordinates per face corner; only the first one each vertex is
+        // used with is kept. a uv seam, where a vertex has different coordinates on either side,
+        // collapses to one side's. the vertices aren't split there, since subdivision and dicing
+        // would then pull the surface apart along the seam
+        if !vertex_uvs.is_empty() {
+            mesh.uvs = (0..mesh.positions.len()).map(|i| vertex_uvs.get(&i).copied().unwrap_or((0.0, 0.0))).collect();
+        }
+
+        Ok(mesh)
+    }
+
+    pub fn set_crease(&mut self, a: usize, b: usize, sharpness: f64) {
+        self.creases.insert(edge_key(a, b), sharpness);
+    }
+
+    pub fn sharpness(&self, a: usize, b: usize) -> f64 {
+        self.creases.get(&edge_key(a, b)).copied().unwrap_or(0.0)
+    }
+}
+
+// edges are looked up by their vertices in order, whichever way round a face goes
+pub fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+struct MeshData {
+    positions: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    triangles: Vec<[usize; 3]>,
+    normals: Vec<[Vec3; 3]>,
+    mat: Box<dyn Material>,
+}
+
+struct MeshTriangle {
+    mesh: Rc<MeshData>,
+    index: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let [i0, i1, i2] = self.mesh.triangles[self.index];
+        let p = [self.mesh.positions[i0], self.mesh.positions[i1], self.mesh.positions[i2]];
+        let (t, b1, b2) = intersect_triangle(r, ray_t, p[0], p[1], p[2])?;
+        let b0 = 1.0 - b1 - b2;
+
+        let uvs = if self.mesh.uvs.is_empty() {
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]
+        } else {
+            [self.mesh.uvs[i0], self.mesh.uvs[i1], self.mesh.uvs[i2]]
+        };
+        let outward_normal = unit_vector(cross(p[1] - p[0], p[2] - p[0]));
+        let normals = &self.mesh.normals[self.index];
+
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, &outward_normal);
+        rec.set_shading_normal(&unit_vector(b0 * normals[0] + b1 * normals[1] + b2 * normals[2]));
+        rec.u = b0 * uvs[0].0 + b1 * uvs[1].0 + b2 * uvs[2].0;
+        rec.v = b0 * uvs[0].1 + b1 * uvs[1].1 + b2 * uvs[2].1;
+        (rec.dpdu, rec.dpdv) = triangle_tangents(p, uvs, outward_normal);
+        rec.mat = Some(&(*self.mesh.mat));
+
+        if !rec.is_opaque(r) {
+            return None;
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [i0, i1, i2] = self.mesh.triangles[self.index];
+        let bbox = Aabb::from_points(self.mesh.positions[i0], self.mesh.positions[i1]);
+        Aabb::surrounding(&bbox, &Aabb::from_points(self.mesh.positions[i2], self.mesh.positions[i2]))
+    }
+}
+
+// a mesh's faces split into triangles sharing one material, in a bvh of their own. shading
+// normals are smoothed across every edge but fully sharp creases and the mesh's boundary
+pub struct TriangleMesh {
+    bvh: BvhNode,
+}
+
+impl TriangleMesh {
+    pub fn new(mesh: &Mesh, mat: impl Material + 'static) -> Self {
+        // fans from each face's first vertex, fine for the convex faces meshes are made of
+        let triangles: Vec<[usize; 3]> = mesh
+            .faces
+            .iter()
+            .flat_map(|face| (1..face.len() - 1).map(move |k| [face[0], face[k], face[k + 1]]))
+            .collect();
+
+        let data = MeshData {
+            positions: mesh.positions.clone(),
+            uvs: mesh.uvs.clone(),
+            normals: corner_normals(mesh, &triangles),
+            triangles,
+            mat: Box::new(mat),
+        };
+
+        let data = Rc::new(data);
+        let objects: Vec<Box<dyn Hittable>> = (0..data.triangles.len())
+            .map(|index| Box::new(MeshTriangle { mesh: Rc::clone(&data), index }) as Box<dyn Hittable>)
+            .collect();
+
+        Self { bvh: BvhNode::from_objects(objects) }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.bvh.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+fn corner_normals(mesh: &Mesh, triangles: &[[usize; 3]]) -> Vec<[Vec3; 3]> {
+    // each triangle corner's normal is the area weighted average over the triangles around its
+    // vertex that it can reach without crossing a hard edge. corners that share a smooth edge are
+    // joined into groups, with corner k of triangle i numbered 3i + k
+    let mut group: Vec<usize> = (0..3 * triangles.len()).collect();
+    fn find(group: &mut [usize], mut corner: usize) -> usize {
+        while group[corner] != corner {
+            group[corner] = group[group[corner]];
+            corner = group[corner];
+        }
+        corner
+    }
+
+    // the triangles along each edge, as the edge's triangle and where it starts in it
+    let mut edge_sides: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+    for (i, triangle) in triangles.iter().enumerate() {
+        for k in 0..3 {
+            edge_sides.entry(edge_key(triangle[k], triangle[(k + 1) % 3])).or_default().push((i, k));
+        }
+    }
+
+    let corner = |(i, k): (usize, usize), vertex: usize| if triangles[i][k] == vertex { 3 * i + k } else { 3 * i + (k + 1) % 3 };
+    for (&(a, b), sides) in &edge_sides {
+        // an edge that subdivision has softened to a sharpness below 1 is already blended smooth
+        if sides.len() != 2 || mesh.sharpness(a, b) >= 1.0 {
+            continue;
+        }
+
+        // join the corners of the two triangles at each end of the edge
+        for vertex in [a, b] {
+            let (x, y) = (find(&mut group, corner(sides[0], vertex)), find(&mut group, corner(sides[1], vertex)));
+            group[x] = y;
+        }
+    }
+
+    let mut sums = vec![Vec3::new(0.0, 0.0, 0.0); group.len()];
+    let face_normals: Vec<Vec3> = triangles
+        .iter()
+        .map(|t| cross(mesh.positions[t[1]] - mesh.positions[t[0]], mesh.positions[t[2]] - mesh.positions[t[0]]))
+        .collect();
+    for corner in 0..group.len() {
+        let root = find(&mut group, corner);
+        sums[root] = sums[root] + face_normals[corner / 3];
+    }
+
+    (0..triangles.len())
+        .map(|i| {
+            [0, 1, 2].map(|k| {
+                let sum = sums[find(&mut group, 3 * i + k)];
+                if sum.length() > 0.0 {
+                    unit_vector(sum)
+                } else {
+                    Vec3::new(0.0, 1.0, 0.0)
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    fn load(name: &str, text: &str) -> io::Result<Mesh> {
+        let path = env::temp_dir().join(format!("raytracing-{}-{}.obj", name, process::id()));
+        fs::write(&path, text).unwrap();
+        let mesh = Mesh::load_obj(path.to_str().unwrap());
+        let _ = fs::remove_file(&path);
+        mesh
+    }
+
+    #[test]
+    fn obj_faces_resolve_relative_indices_and_skip_normals() {
+        let text = "# a square, then a triangle on it given with relative indices\n\
+            v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+            vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+            vn 0 0 1\n\
+            f 1/1/1 2/2/1 3/3/1 4/4/1\n\
+            v 0.5 2 0\n\
+            f -2//1 -3//1 -1//1\n\
+            g ignored\nusemtl ignored\n";
+        let mesh = load("indices", text).unwrap();
+
+        assert_eq!(mesh.positions.len(), 5);
+        assert_eq!(mesh.faces, vec![vec![0, 1, 2, 3], vec![3, 2, 4]]);
+        assert_eq!(mesh.uvs.len(), 5);
+        assert_eq!(mesh.uvs[2], (1.0, 1.0));
+        assert_eq!(mesh.uvs[4], (0.0, 0.0), "a vertex only used without texture coordinates gets 0, 0");
+    }
+
+    #[test]
+    fn obj_crease_tags_set_edge_sharpness() {
+        let text = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nt crease 2/1/0 1 0 2.5\nt crease 2/1/0 2 1 inf\n";
+        let mesh = load("creases", text).unwrap();
+
+        assert_eq!(mesh.sharpness(0, 1), 2.5);
+        assert_eq!(mesh.sharpness(1, 2), f64::INFINITY);
+        assert_eq!(mesh.sharpness(2, 0), 0.0);
+    }
+
+    #[test]
+    fn malformed_obj_lines_are_invalid_data() {
+        for (name, text) in [
+            ("short-vertex", "v 0 0\n"),
+            ("bad-number", "v 0 zero 0\n"),
+            ("short-uv", "vt 0.5\n"),
+            ("out-of-range", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n"),
+            ("zero-index", "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n"),
+            ("two-corners", "v 0 0 0\nv 1 0 0\nf 1 2\n"),
+            ("short-crease", "t crease 2/1/0 0 1\n"),
+        ] {
+            let error = load(name, text).err().unwrap_or_else(|| panic!("{name} should not load"));
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{name}");
+        }
+    }
+
+    fn fold(sharpness: f64) -> Vec<[Vec3; 3]> {
+        // two triangles bent along the edge from vertex 0 to 1, which is creased
+        let mut mesh = Mesh::new(
+            vec![Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Point3::new(0.5, 1.0, 0.0), Point3::new(0.5, 0.0, -1.0)],
+            vec![vec![0, 1, 2], vec![1, 0, 3]],
+        );
+        mesh.set_crease(0, 1, sharpness);
+        corner_normals(&mesh, &[[0, 1, 2], [1, 0, 3]])
+    }
+
+    #[test]
+    fn only_fully_sharp_creases_shade_hard() {
+        // corner 0 of the first triangle and corner 1 of the second are both vertex 0
+        let soft = fold(0.5);
+        assert!((soft[0][0] - soft[1][1]).length() < 1e-12, "a softened crease should be smooth");
+
+        let sharp = fold(1.0);
+        assert!((sharp[0][0] - sharp[1][1]).length() > 0.5, "a fully sharp crease should be hard");
+    }
+}
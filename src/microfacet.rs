@@ -0,0 +1,123 @@
+use crate::common::PI;
+use crate::vector::{cross, dot, unit_vector, Color, Vec3};
+
+// GGX / Trowbridge-Reitz microfacet distribution with Smith masking-shadowing. all directions are
+// in the local shading frame, where the macro surface normal is +z
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self {
+        // a perfectly smooth surface is a delta distribution; keep a sliver of roughness instead so
+        // the same code handles it
+        Self { alpha_x: alpha_x.max(1e-4), alpha_y: alpha_y.max(1e-4) }
+    }
+
+    pub fn roughness_to_alpha(roughness: f64) -> f64 {
+        // squaring gives a perceptually more even response to the roughness parameter
+        roughness * roughness
+    }
+
+    pub fn lambda(&self, w: Vec3) -> f64 {
+        let cos2_theta = w.z() * w.z();
+        if cos2_theta <= 0.0 {
+            return 0.0;
+        }
+
+        let alpha2_tan2_theta = ((self.alpha_x * w.x()).powi(2) + (self.alpha_y * w.y()).powi(2)) / cos2_theta;
+        ((1.0 + alpha2_tan2_theta).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: Vec3) -> f64 {
+        // fraction of microfacets facing w that are visible from w
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f64 {
+        // height-correlated masking-shadowing
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    pub fn sample_wm(&self, w: Vec3, u: (f64, f64)) -> Vec3 {
+        // samples a microfacet normal from the distribution of normals visible from w
+        // (Heitz 2018, "Sampling the GGX Distribution of Visible Normals")
+        let wh = unit_vector(Vec3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()));
+
+        let len2 = wh.x() * wh.x() + wh.y() * wh.y();
+        let t1 = if len2 > 0.0 { Vec3::new(-wh.y(), wh.x(), 0.0) / len2.sqrt() } else { Vec3::new(1.0, 0.0, 0.0) };
+        let t2 = cross(wh, t1);
+
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + wh.z());
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * wh;
+        unit_vector(Vec3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(1e-6)))
+    }
+}
+
+pub fn fresnel_conductor(cos_theta_i: f64, eta: Color, k: Color) -> Color {
+    // fresnel_complex evaluated per color channel
+    Color::new(
+        fresnel_complex(cos_theta_i, eta.x(), k.x()),
+        fresnel_complex(cos_theta_i, eta.y(), k.y()),
+        fresnel_complex(cos_theta_i, eta.z(), k.z()),
+    )
+}
+
+pub fn fresnel_complex(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    // unpolarized Fresnel reflectance of a conductor with complex index of refraction eta + ik
+    let cos2 = cos_theta_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let eta2 = eta * eta;
+    let k2 = k * k;
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t2 = 2.0 * cos_theta_i * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rp + rs)
+}
+
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    // unpolarized Fresnel reflectance at a dielectric interface, where eta is the ratio of the
+    // index of refraction on the far side of the interface to the near side
+    let (cos_theta_i, eta) = if cos_theta_i < 0.0 { (-cos_theta_i, 1.0 / eta) } else { (cos_theta_i, eta) };
+    let cos_theta_i = cos_theta_i.min(1.0);
+
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        // total internal reflection
+        return 1.0;
+    }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    let r_parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+pub fn refract_microfacet(wo: Vec3, wm: Vec3, eta: f64) -> Option<Vec3> {
+    // direction of wo refracted through a microfacet with normal wm, unless it is totally
+    // internally reflected. both wo and the result point away from the surface
+    let cos_theta_i = dot(&wo, &wm);
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(-wo / eta + (cos_theta_i / eta - cos_theta_t) * wm)
+}
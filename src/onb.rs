@@ -0,0 +1,43 @@
+use crate::vector::{cross, dot, unit_vector, Vec3};
+
+// orthonormal basis around a surface normal w, used to move directions in and out of a local
+// shading frame where the normal is +z
+pub struct Onb {
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Self {
+        let w = unit_vector(n);
+        let a = if w.x().abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = unit_vector(cross(w, a));
+        let u = cross(w, v);
+
+        Self { u, v, w }
+    }
+
+    pub fn from_tangent(n: Vec3, tangent: Vec3) -> Self {
+        // basis whose u axis follows the tangent, projected off the normal, so that anything
+        // anisotropic lines up with the surface's parameterization. surfaces without a usable
+        // tangent get an arbitrary one
+        let w = unit_vector(n);
+        let u = tangent - dot(&tangent, &w) * w;
+        if u.length_squared() <= 1e-12 * tangent.length_squared() {
+            return Self::new(n);
+        }
+        let u = unit_vector(u);
+        let v = cross(w, u);
+
+        Self { u, v, w }
+    }
+
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(dot(&a, &self.u), dot(&a, &self.v), dot(&a, &self.w))
+    }
+
+    pub fn to_world(&self, a: Vec3) -> Vec3 {
+        a.x() * self.u + a.y() * self.v + a.z() * self.w
+    }
+}
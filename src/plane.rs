@@ -0,0 +1,65 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::{self, Interval};
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{dot, unit_vector, Point3, Vec3};
+
+// infinite plane through `point`, facing along `normal`. the texture coordinates are distances
+// along the plane from `point`, so image textures repeat every unit
+pub struct Plane {
+    point: Point3,
+    normal: Vec3,
+    frame: Onb,
+    mat: Box<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat: impl Material + 'static) -> Self {
+        let normal = unit_vector(normal);
+        Self { point, normal, frame: Onb::new(normal), mat: Box::new(mat) }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = dot(&self.normal, &r.direction());
+        if denom.abs() < 1e-8 {
+            // the ray runs parallel to the plane
+            return None;
+        }
+
+        let t = dot(&self.normal, &(self.point - r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let p = r.at(t);
+        let local = self.frame.to_local(p - self.point);
+
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = p;
+        rec.set_face_normal(r, &self.normal);
+        (rec.u, rec.v) = (local.x(), local.y());
+        (rec.dpdu, rec.dpdv) = (self.frame.to_world(Vec3::new(1.0, 0.0, 0.0)), self.frame.to_world(Vec3::new(0.0, 1.0, 0.0)));
+        rec.mat = Some(&(*self.mat));
+
+        if !rec.is_opaque(r) {
+            return None;
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // unbounded, except across an axis-aligned plane
+        let axis = |n: f64, p: f64| if n.abs() == 1.0 { Interval::new(p, p) } else { interval::UNIVERSE };
+        Aabb::new(
+            axis(self.normal.x(), self.point.x()),
+            axis(self.normal.y(), self.point.y()),
+            axis(self.normal.z(), self.point.z()),
+        )
+    }
+}
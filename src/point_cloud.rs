@@ -0,0 +1,625 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::aabb::{self, Aabb};
+use crate::hittable::{reserve_surfaces, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::spectrum::{uplift, SampledSpectrum, SampledWavelengths};
+use crate::sphere::Sphere;
+use crate::texture::srgb_to_linear;
+use crate::vector::{dot, unit_vector, Color, Point3, Vec3};
+
+// most points in a leaf of a point cloud's bvh
+const LEAF_POINTS: usize = 8;
+
+// deepest a point cloud's bvh can be, which a median split keeps well clear of for any cloud
+// that fits in u32 indices
+const MAX_BVH_DEPTH: usize = 64;
+
+#[derive(Copy, Clone)]
+pub enum PointShape {
+    // a ball around each point
+    Sphere,
+    // a disc around each point, turned to face whichever ray is looking at it, as splats are
+    Disc,
+}
+
+// points kept as compactly as files hold them: single precision positions, and 8 bit sRGB colors.
+// radii and colors can be left empty, for every point to share `radius` or be white
+pub struct Points {
+    pub positions: Vec<[f32; 3]>,
+    pub radii: Vec<f32>,
+    pub colors: Vec<[u8; 3]>,
+    pub radius: f64,
+}
+
+impl Points {
+    pub fn new(radius: f64) -> Self {
+        Self { positions: Vec::new(), radii: Vec::new(), colors: Vec::new(), radius }
+    }
+
+    pub fn push(&mut self, p: Point3, color: [u8; 3]) {
+        self.positions.push([p.x() as f32, p.y() as f32, p.z() as f32]);
+        self.colors.push(color);
+    }
+
+    pub fn position(&self, i: usize) -> Point3 {
+        let [x, y, z] = self.positions[i];
+        Point3::new(x as f64, y as f64, z as f64)
+    }
+
+    pub fn point_radius(&self, i: usize) -> f64 {
+        self.radii.get(i).map_or(self.radius, |&radius| radius as f64)
+    }
+
+    pub fn load(filename: &str, radius: f64) -> io::Result<Self> {
+        // a ply file, or otherwise an xyz text file. `radius` is for points without one of their own
+        if filename.to_lowercase().ends_with(".ply") {
+            Self::load_ply(filename, radius)
+        } else {
+            Self::load_xyz(filename, radius)
+        }
+    }
+
+    pub fn load_xyz(filename: &str, radius: f64) -> io::Result<Self> {
+        // a point per line as `x y z`, optionally followed by `r g b` from 0 to 255. a fourth or
+        // fifth column, such as intensity, is skipped, and so are blank lines and # comments
+        let invalid = |line: &str| io::Error::new(io::ErrorKind::InvalidData, format!("bad xyz line: {line}"));
+        let r = BufReader::new(File::open(filename)?);
+
+        let mut points = Self::new(radius);
+        for line in r.lines() {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() || fields[0].starts_with('#') {
+                continue;
+            }
+
+            let values: Vec<f64> = fields.iter().map(|x| x.parse()).collect::<Result<_, _>>().map_err(|_| invalid(&line))?;
+            if values.len() < 3 {
+                return Err(invalid(&line));
+            }
+            points.positions.push([values[0] as f32, values[1] as f32, values[2] as f32]);
+
+            // points before the first one with a color are white
+            if values.len() >= 6 {
+                points.colors.resize(points.positions.len() - 1, [255; 3]);
+                points.colors.push([values[3], values[4], values[5]].map(|c| c.clamp(0.0, 255.0) as u8));
+            } else if !points.colors.is_empty() {
+                points.colors.push([255; 3]);
+            }
+        }
+
+        Ok(points)
+    }
+
+    pub fn load_ply(filename: &str, radius: f64) -> io::Result<Self> {
+        // the vertex element of an ascii or binary little-endian ply file: x, y and z, plus red,
+        // green, blue and radius if it has them. the vertices have to come first, and any faces
+        // after them are ignored
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut r = BufReader::new(File::open(filename)?);
+
+        let mut line = String::new();
+        r.read_line(&mut line)?;
+        if line.trim() != "ply" {
+            return Err(invalid("not a ply file"));
+        }
+
+        let mut binary = false;
+        let mut count = None;
+        let mut reading_vertices = false;
+        let mut properties: Vec<(PlyType, String)> = Vec::new();
+        loop {
+            line.clear();
+            if r.read_line(&mut line)? == 0 {
+                return Err(invalid("ply header has no end"));
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            match fields.as_slice() {
+                ["format", "ascii", _] => binary = false,
+                ["format", "binary_little_endian", _] => binary = true,
+                ["format", ..] => return Err(invalid(&format!("unsupported ply format: {}", line.trim()))),
+                ["element", "vertex", n] if count.is_none() => {
+                    count = Some(n.parse::<usize>().map_err(|_| invalid("bad ply vertex count"))?);
+                    reading_vertices = true;
+                }
+                ["element", ..] => {
+                    if count.is_none() {
+                        return Err(invalid("ply vertex element has to come first"));
+                    }
+                    reading_vertices = false;
+                }
+                ["property", "list", ..] if reading_vertices => return Err(invalid("ply vertices can't have list properties")),
+                ["property", kind, name] if reading_vertices => {
+                    let kind = PlyType::parse(kind).ok_or_else(|| invalid(&format!("bad ply property type: {kind}")))?;
+                    properties.push((kind, name.to_string()));
+                }
+                ["end_header"] => break,
+                _ => (),
+            }
+        }
+
+        let count = count.ok_or_else(|| invalid("ply file has no vertices"))?;
+        let find = |names: &[&str]| properties.iter().position(|(_, name)| names.contains(&name.as_str()));
+        let (Some(x), Some(y), Some(z)) = (find(&["x"]), find(&["y"]), find(&["z"])) else {
+            return Err(invalid("ply vertices have no position"));
+        };
+        let color = match (find(&["red", "r", "diffuse_red"]), find(&["green", "g", "diffuse_green"]), find(&["blue", "b", "diffuse_blue"])) {
+            (Some(red), Some(green), Some(blue)) => Some([red, green, blue]),
+            _ => None,
+        };
+        let point_radius = find(&["radius"]);
+
+        let mut points = Self::new(radius);
+        points.positions.reserve(count);
+        let mut values = vec![0.0; properties.len()];
+        let row_size: usize = properties.iter().map(|(kind, _)| kind.size()).sum();
+        let mut row = vec![0; row_size];
+
+        for _ in 0..count {
+            if binary {
+                r.read_exact(&mut row)?;
+                let mut offset = 0;
+                for (value, (kind, _)) in values.iter_mut().zip(&properties) {
+                    *value = kind.decode(&row[offset..offset + kind.size()]);
+                    offset += kind.size();
+                }
+            } else {
+                line.clear();
+                r.read_line(&mut line)?;
+                let fields: Vec<f64> = line.split_whitespace().map(str::parse).collect::<Result<_, _>>().map_err(|_| invalid("bad ply vertex"))?;
+                if fields.len() < values.len() {
+                    return Err(invalid("bad ply vertex"));
+                }
+                let n = values.len();
+                values.copy_from_slice(&fields[..n]);
+            }
+
+            points.positions.push([values[x] as f32, values[y] as f32, values[z] as f32]);
+            if let Some(channels) = color {
+                // integer colors run up to 255, and floating point ones up to 1
+                points.colors.push(channels.map(|c| {
+                    let scale = if properties[c].0.is_float() { 255.0 } else { 1.0 };
+                    (scale * values[c]).round().clamp(0.0, 255.0) as u8
+                }));
+            }
+            if let Some(radius) = point_radius {
+                points.radii.push(values[radius] as f32);
+            }
+        }
+
+        Ok(points)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Self::Int8),
+            "uchar" | "uint8" => Some(Self::UInt8),
+            "short" | "int16" => Some(Self::Int16),
+            "ushort" | "uint16" => Some(Self::UInt16),
+            "int" | "int32" => Some(Self::Int32),
+            "uint" | "uint32" => Some(Self::UInt32),
+            "float" | "float32" => Some(Self::Float32),
+            "double" | "float64" => Some(Self::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, Self::Float32 | Self::Float64)
+    }
+
+    fn decode(&self, b: &[u8]) -> f64 {
+        match self {
+            Self::Int8 => b[0] as i8 as f64,
+            Self::UInt8 => b[0] as f64,
+            Self::Int16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            Self::UInt16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            Self::Int32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Self::UInt32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Self::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            Self::Float64 => f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]),
+        }
+    }
+}
+
+// the material of a point cloud's points, tinted by each point's color. the cloud's hits carry
+// the point's index as their primitive
+struct PointMaterial {
+    colors: Vec<[u8; 3]>,
+    linear: [f64; 256],
+    mat: Box<dyn Material>,
+}
+
+impl PointMaterial {
+    fn color(&self, i: usize) -> Color {
+        match self.colors.get(i) {
+            Some(&[r, g, b]) => Color::new(self.linear[r as usize], self.linear[g as usize], self.linear[b as usize]),
+            None => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Material for PointMaterial {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let (attenuation, scattered) = self.mat.scatter(r_in, record, sampler)?;
+        Some((self.color(record.primitive) * attenuation, scattered))
+    }
+
+    fn scatter_spectral(
+        &self,
+        r_in: Ray,
+        record: &HitRecord,
+        lambda: &mut SampledWavelengths,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let tint = self.color(record.primitive);
+        let (attenuation, scattered) = self.mat.scatter_spectral(r_in, record, lambda, sampler)?;
+        Some((uplift(tint, lambda) * attenuation, scattered))
+    }
+
+    fn opacity(&self, u: f64, v: f64, p: &Point3) -> f64 {
+        self.mat.opacity(u, v, p)
+    }
+
+    // emission is only given the surface coordinates, not which point was hit, so it isn't tinted
+    fn emitted(&self, u: f64, v: f64, p: &Point3) -> Color {
+        self.mat.emitted(u, v, p)
+    }
+
+    fn emitted_spectral(&self, u: f64, v: f64, p: &Point3, lambda: &SampledWavelengths) -> SampledSpectrum {
+        self.mat.emitted_spectral(u, v, p, lambda)
+    }
+}
+
+// a bvh node, stored flat: a leaf holds `count` points from `offset` on, and an inner node has
+// a count of 0, its left child right after it and its right child at `offset`
+struct Node {
+    bbox: Aabb,
+    offset: u32,
+    count: u32,
+    axis: u8,
+}
+
+// many points drawn as spheres or discs sharing one material, which each point's color tints.
+// the points are sorted into the order of the leaves of a bvh of their own, so nothing is kept
+// per point beyond its position, radius and color
+pub struct PointCloud {
+    points: Points,
+    nodes: Vec<Node>,
+    shape: PointShape,
+    mat: PointMaterial,
+    first_surface: usize,
+}
+
+impl PointCloud {
+    pub fn new(mut points: Points, shape: PointShape, mat: impl Material + 'static) -> Self {
+        let count = points.positions.len();
+        assert!(count > 0 && count <= u32::MAX as usize, "Could not build a point cloud from {count} points.");
+        assert!(
+            points.radii.is_empty() || points.radii.len() == count,
+            "A point cloud of {count} points has {} radii.",
+            points.radii.len()
+        );
+        assert!(
+            points.colors.is_empty() || points.colors.len() == count,
+            "A point cloud of {count} points has {} colors.",
+            points.colors.len()
+        );
+
+        let mut order: Vec<u32> = (0..count as u32).collect();
+        let mut nodes = Vec::with_capacity(2 * count / LEAF_POINTS + 1);
+        build(&points, &mut nodes, &mut order, 0, 0);
+
+        // put the points in the order the leaves hold them
+        points.positions = order.iter().map(|&i| points.positions[i as usize]).collect();
+        if !points.radii.is_empty() {
+            points.radii = order.iter().map(|&i| points.radii[i as usize]).collect();
+        }
+        let colors = if points.colors.is_empty() {
+            Vec::new()
+        } else {
+            order.iter().map(|&i| points.colors[i as usize]).collect()
+        };
+
+        let mut linear = [0.0; 256];
+        for (i, value) in linear.iter_mut().enumerate() {
+            *value = srgb_to_linear(i as f64 / 255.0);
+        }
+        points.colors = Vec::new();
+
+        let mat = PointMaterial { colors, linear, mat: Box::new(mat) };
+        Self { points, nodes, shape, mat, first_surface: reserve_surfaces(count) }
+    }
+
+    fn hit_point(&self, r: &Ray, ray_t: Interval, i: usize) -> Option<HitRecord<'_>> {
+        let center = self.points.position(i);
+        let radius = self.points.point_radius(i);
+        let oc = center - r.origin();
+        let a = r.direction().length_squared();
+
+        let (t, outward_normal) = match self.shape {
+            PointShape::Sphere => {
+                let h = dot(&r.direction(), &oc);
+                let discriminant = h * h - a * (oc.length_squared() - radius * radius);
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let sqrtd = discriminant.sqrt();
+                let t = [(h - sqrtd) / a, (h + sqrtd) / a].into_iter().find(|&t| ray_t.surrounds(t))?;
+                (t, (r.at(t) - center) / radius)
+            }
+            PointShape::Disc => {
+                // a disc turns to face each ray, so a ray scattered off it would meet it again up
+                // to a radius away. only that disc is skipped; its neighbours still block the ray
+                if r.source() == Some(self.first_surface + i) {
+                    return None;
+                }
+                let t = dot(&r.direction(), &oc) / a;
+                if !ray_t.surrounds(t) || (r.at(t) - center).length_squared() > radius * radius {
+                    return None;
+                }
+                (t, -unit_vector(r.direction()))
+            }
+        };
+
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, &outward_normal);
+        rec.primitive = i;
+        rec.surface = Some(self.first_surface + i);
+        match self.shape {
+            PointShape::Sphere => {
+                (rec.u, rec.v) = Sphere::get_sphere_uv(&outward_normal);
+                (rec.dpdu, rec.dpdv) = Sphere::get_sphere_tangents(&(rec.p - center));
+            }
+            PointShape::Disc => {
+                // a disc turns to face each ray, so its coordinates are across the disc as seen
+                let frame = Onb::new(outward_normal);
+                let dpdu = 2.0 * radius * frame.to_world(Vec3::new(1.0, 0.0, 0.0));
+                let dpdv = 2.0 * radius * frame.to_world(Vec3::new(0.0, 1.0, 0.0));
+                let local = rec.p - center;
+                rec.u = 0.5 + dot(&local, &dpdu) / dpdu.length_squared();
+                rec.v = 0.5 + dot(&local, &dpdv) / dpdv.length_squared();
+                (rec.dpdu, rec.dpdv) = (dpdu, dpdv);
+            }
+        }
+        rec.mat = Some(&self.mat);
+
+        if !rec.is_opaque(r) {
+            return None;
+        }
+
+        Some(rec)
+    }
+}
+
+impl Hittable for PointCloud {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // walk the bvh with a stack, visiting the child nearer the ray's origin first so that
+        // hits there cut the search short in the farther one
+        let mut stack = [0u32; MAX_BVH_DEPTH];
+        let mut size = 1;
+        let mut closest: Option<HitRecord> = None;
+
+        while size > 0 {
+            size -= 1;
+            let node = &self.nodes[stack[size] as usize];
+            let max = closest.as_ref().map_or(ray_t.max, |record| record.t);
+            if !node.bbox.hit(r, Interval::new(ray_t.min, max)) {
+                continue;
+            }
+
+            if node.count > 0 {
+                for i in node.offset..node.offset + node.count {
+                    let max = closest.as_ref().map_or(ray_t.max, |record| record.t);
+                    if let Some(rec) = self.hit_point(r, Interval::new(ray_t.min, max), i as usize) {
+                        closest = Some(rec);
+                    }
+                }
+            } else {
+                let (left, right) = (stack[size] + 1, node.offset);
+                let (near, far) = if r.direction()[node.axis as usize] < 0.0 { (right, left) } else { (left, right) };
+                stack[size] = far;
+                stack[size + 1] = near;
+                size += 2;
+            }
+        }
+
+        closest
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes[0].bbox
+    }
+}
+
+fn build(points: &Points, nodes: &mut Vec<Node>, order: &mut [u32], offset: usize, depth: usize) -> u32 {
+    // splits the points at the median along the axis their centers are most spread out on.
+    // returns the index of the new node
+    let point_box = |i: u32| {
+        let (p, radius) = (points.position(i as usize), points.point_radius(i as usize));
+        Aabb::from_points(p - Vec3::new(radius, radius, radius), p + Vec3::new(radius, radius, radius))
+    };
+    let bbox = order.iter().fold(aabb::EMPTY, |bbox, &i| Aabb::surrounding(&bbox, &point_box(i)));
+
+    let index = nodes.len();
+    nodes.push(Node { bbox, offset: offset as u32, count: order.len() as u32, axis: 0 });
+    if order.len() <= LEAF_POINTS || depth + 2 >= MAX_BVH_DEPTH {
+        return index as u32;
+    }
+
+    let centers = order.iter().fold(aabb::EMPTY, |bbox, &i| {
+        let p = points.position(i as usize);
+        Aabb::surrounding(&bbox, &Aabb::from_points(p, p))
+    });
+    let axis = centers.longest_axis();
+
+    let middle = order.len() / 2;
+    order.select_nth_unstable_by(middle, |&a, &b| points.positions[a as usize][axis].total_cmp(&points.positions[b as usize][axis]));
+    let (left, right) = order.split_at_mut(middle);
+    build(points, nodes, left, offset, depth + 1);
+    let right = build(points, nodes, right, offset + middle, depth + 1);
+
+    nodes[index] = Node { bbox, offset: right, count: 0, axis: axis as u8 };
+    index as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    use crate::common::{random_f64, seed_random, INFINITY};
+    use crate::material::Lambertian;
+
+    fn white() -> Lambertian {
+        Lambertian::new(Color::new(1.0, 1.0, 1.0))
+    }
+
+    fn load(name: &str, bytes: &[u8]) -> io::Result<Points> {
+        let path = env::temp_dir().join(format!("raytracing-{}-{}", process::id(), name));
+        fs::write(&path, bytes).unwrap();
+        let points = Points::load(path.to_str().unwrap(), 0.1);
+        let _ = fs::remove_file(&path);
+        points
+    }
+
+    #[test]
+    fn ascii_ply_with_float_colors() {
+        let text = "ply\nformat ascii 1.0\ncomment float colors run up to 1\n\
+            element vertex 2\nproperty float x\nproperty float y\nproperty float z\n\
+            property float red\nproperty float green\nproperty float blue\n\
+            element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+            1 2 3 1 0.5 0\n-1 -2 -3 0 0 0.25\n3 0 1 1\n";
+        let points = load("float.ply", text.as_bytes()).unwrap();
+
+        assert_eq!(points.positions, vec![[1.0, 2.0, 3.0], [-1.0, -2.0, -3.0]]);
+        assert_eq!(points.colors, vec![[255, 128, 0], [0, 0, 64]]);
+        assert!(points.radii.is_empty());
+        assert_eq!(points.point_radius(1), 0.1);
+    }
+
+    #[test]
+    fn binary_ply_with_uchar_colors_and_radii() {
+        let header = "ply\nformat binary_little_endian 1.0\nelement vertex 2\n\
+            property double x\nproperty double y\nproperty double z\n\
+            property uchar red\nproperty uchar green\nproperty uchar blue\nproperty float radius\nend_header\n";
+        let mut bytes = header.as_bytes().to_vec();
+        for (p, color, radius) in [([1.5f64, -2.0, 0.25], [10, 20, 30], 0.5f32), ([0.0, 4.0, -8.0], [255, 0, 7], 2.0)] {
+            for x in p {
+                bytes.extend(x.to_le_bytes());
+            }
+            bytes.extend(color);
+            bytes.extend(radius.to_le_bytes());
+        }
+        let points = load("binary.ply", &bytes).unwrap();
+
+        assert_eq!(points.positions, vec![[1.5, -2.0, 0.25], [0.0, 4.0, -8.0]]);
+        assert_eq!(points.colors, vec![[10, 20, 30], [255, 0, 7]]);
+        assert_eq!(points.radii, vec![0.5, 2.0]);
+    }
+
+    #[test]
+    fn ply_without_positions_is_invalid() {
+        let text = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\nproperty float y\nend_header\n1 2\n";
+        let error = load("flat.ply", text.as_bytes()).err().expect("loaded points without z");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let error = load("short.ply", b"ply\nformat ascii 1.0\nelement vertex 2\nproperty float x\nproperty float y\nproperty float z\nend_header\n1 2 3\n");
+        assert!(error.is_err(), "loaded fewer vertices than the header promised");
+    }
+
+    #[test]
+    fn xyz_with_colored_and_plain_lines() {
+        // plain points are white, including ones before the first colored point
+        let text = "# x y z [r g b]\n0 0 0\n\n1 1 1 200 100 50\n2 2 2\n3 3 3 0.5\n4 4 4 1 2 3 0.9\n";
+        let points = load("mixed.xyz", text.as_bytes()).unwrap();
+
+        assert_eq!(points.positions.len(), 5);
+        assert_eq!(points.colors, vec![[255, 255, 255], [200, 100, 50], [255, 255, 255], [255, 255, 255], [1, 2, 3]]);
+
+        let error = load("bad.xyz", b"0 0\n").err().expect("loaded a point with two coordinates");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn bvh_hits_match_a_linear_scan() {
+        seed_random(3);
+        for shape in [PointShape::Sphere, PointShape::Disc] {
+            let mut points = Points::new(0.05);
+            for _ in 0..2000 {
+                points.push(Point3::new(random_f64(), random_f64(), random_f64()), [255; 3]);
+            }
+            for _ in 0..2000 {
+                points.radii.push((0.01 + 0.05 * random_f64()) as f32);
+            }
+            let cloud = PointCloud::new(points, shape, white());
+
+            for _ in 0..500 {
+                let origin = Point3::new(4.0 * random_f64() - 1.5, 4.0 * random_f64() - 1.5, 4.0 * random_f64() - 1.5);
+                let target = Point3::new(random_f64(), random_f64(), random_f64());
+                let r = Ray::new(origin, target - origin);
+                let ray_t = Interval::new(0.001, INFINITY);
+
+                let nearest = (0..cloud.points.positions.len())
+                    .filter_map(|i| cloud.hit_point(&r, ray_t, i))
+                    .min_by(|a, b| a.t.total_cmp(&b.t))
+                    .map(|hit| hit.t);
+                assert_eq!(cloud.hit(&r, ray_t).map(|hit| hit.t), nearest);
+            }
+        }
+    }
+
+    #[test]
+    fn ray_leaving_a_disc_still_meets_overlapping_neighbours() {
+        // two discs of radius 1 whose centres are half a radius apart, both facing the ray
+        let mut points = Points::new(1.0);
+        points.push(Point3::new(0.0, 0.0, 0.0), [255, 255, 255]);
+        points.push(Point3::new(0.0, 0.0, 0.5), [255, 255, 255]);
+        let cloud = PointCloud::new(points, PointShape::Disc, white());
+
+        let camera_ray = Ray::new(Point3::new(0.0, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let first = cloud.hit(&camera_ray, Interval::new(0.001, INFINITY)).unwrap();
+        assert!(first.p.z().abs() < 1e-9, "the nearer disc is hit first");
+
+        // carrying on from the first disc reaches the second, but never the first again
+        let onwards = Ray::new(first.p, Vec3::new(0.0, 0.0, 1.0)).leaving(first.surface);
+        let second = cloud.hit(&onwards, Interval::new(0.001, INFINITY)).unwrap();
+        assert!((second.p.z() - 0.5).abs() < 1e-9);
+        assert_ne!(second.surface, first.surface);
+
+        let sideways = Ray::new(first.p, Vec3::new(0.0, 1.0, 0.0)).leaving(first.surface);
+        assert!(cloud.hit(&sideways, Interval::new(0.001, INFINITY)).is_none_or(|hit| hit.surface != first.surface));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::hittable_list::HittableList;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::{cross, dot, unit_vector, Point3, Vec3};
+
+// parallelogram with corner q and sides u and v. the texture coordinates run from 0 to 1 along
+// each side, and the front face is the one u and v wind counterclockwise around
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    normal: Vec3,
+    d: f64,
+    mat: Box<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: impl Material + 'static) -> Self {
+        let n = cross(u, v);
+        let normal = unit_vector(n);
+        let d = dot(&normal, &q);
+
+        // w turns a point on the plane into its coordinates along u and v
+        let w = n / dot(&n, &n);
+
+        Self { q, u, v, w, normal, d, mat: Box::new(mat) }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let denom = dot(&self.normal, &r.direction());
+        if denom.abs() < 1e-8 {
+            // the ray runs parallel to the plane
+            return None;
+        }
+
+        let t = (self.d - dot(&self.normal, &r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return None;
+        }
+
+        let p = r.at(t);
+        let planar = p - self.q;
+        let alpha = dot(&self.w, &cross(planar, self.v));
+        let beta = dot(&self.w, &cross(self.u, planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = p;
+        rec.set_face_normal(r, &self.normal);
+        (rec.u, rec.v) = (alpha, beta);
+        (rec.dpdu, rec.dpdv) = (self.u, self.v);
+        rec.mat = Some(&(*self.mat));
+
+        if !rec.is_opaque(r) {
+            return None;
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let diagonal1 = Aabb::from_points(self.q, self.q + self.u + self.v);
+        let diagonal2 = Aabb::from_points(self.q + self.u, self.q + self.v);
+        Aabb::surrounding(&diagonal1, &diagonal2)
+    }
+}
+
+pub fn make_box<M: Material + 'static>(a: Point3, b: Point3, mat: impl Fn() -> M) -> HittableList {
+    // the six sides of the axis-aligned box with opposite corners a and b, facing out. every side
+    // gets its own copy of the material
+    let mut sides = HittableList::new();
+
+    let min = Point3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vec3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z() - min.z());
+
+    sides.add(Quad::new(Point3::new(min.x(), min.y(), max.z()), dx, dy, mat())); // front
+    sides.add(Quad::new(Point3::new(max.x(), min.y(), max.z()), -dz, dy, mat())); // right
+    sides.add(Quad::new(Point3::new(max.x(), min.y(), min.z()), -dx, dy, mat())); // back
+    sides.add(Quad::new(Point3::new(min.x(), min.y(), min.z()), dz, dy, mat())); // left
+    sides.add(Quad::new(Point3::new(min.x(), max.y(), max.z()), dx, -dz, mat())); // top
+    sides.add(Quad::new(Point3::new(min.x(), min.y(), min.z()), dx, dz, mat())); // bottom
+
+    sides
+}
@@ -2,12 +2,22 @@ use crate::vector::{Point3, Vec3};
 
 pub struct Ray {
     origin: Point3,
-    direction: Vec3
+    direction: Vec3,
+    source: Option<usize>, // the surface the ray is leaving, for shapes that can't tell by distance
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self {origin, direction}
+        Self {origin, direction, source: None}
+    }
+
+    pub fn leaving(mut self, source: Option<usize>) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn source(&self) -> Option<usize> {
+        self.source
     }
 
     pub fn direction(&self) -> Vec3 {
@@ -0,0 +1,385 @@
+use std::str::FromStr;
+
+use crate::common::{hash, random_f64};
+
+// largest f64 below one, so scaled integer samples never round up to 1.0
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// bases for the Halton sampler, one per dimension
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+pub trait Sampler {
+    // start generating sample number `sample_index` of pixel i, j. dimensions are handed out in
+    // the order they are requested: pixel jitter, lens, then BSDF dimensions for each bounce
+    fn start_pixel_sample(&mut self, i: i32, j: i32, sample_index: i32);
+    fn get_1d(&mut self) -> f64;
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SamplerType {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+impl SamplerType {
+    pub fn create(&self, samples_per_pixel: i32) -> Box<dyn Sampler> {
+        match self {
+            SamplerType::Independent => Box::new(IndependentSampler),
+            SamplerType::Stratified => Box::new(StratifiedSampler::new(samples_per_pixel)),
+            SamplerType::Halton => Box::new(HaltonSampler::new()),
+            SamplerType::Sobol => Box::new(SobolSampler::new()),
+        }
+    }
+}
+
+impl FromStr for SamplerType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "independent" | "uniform" => Ok(SamplerType::Independent),
+            "stratified" => Ok(SamplerType::Stratified),
+            "halton" => Ok(SamplerType::Halton),
+            "sobol" => Ok(SamplerType::Sobol),
+            _ => Err(format!("unknown sampler '{}'", s)),
+        }
+    }
+}
+
+// independent uniform random numbers for every dimension
+pub struct IndependentSampler;
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, _i: i32, _j: i32, _sample_index: i32) {}
+
+    fn get_1d(&mut self) -> f64 {
+        random_f64()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (random_f64(), random_f64())
+    }
+}
+
+// jittered sampling: each dimension is split into samples_per_pixel strata and every sample of a
+// pixel lands in a different stratum. strata are visited in a per-pixel, per-dimension shuffled
+// order so that dimensions are not correlated with each other
+pub struct StratifiedSampler {
+    samples_per_pixel: u32,
+    x_strata: u32,
+    y_strata: u32,
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl StratifiedSampler {
+    pub fn new(samples_per_pixel: i32) -> Self {
+        let samples_per_pixel = samples_per_pixel.max(1) as u32;
+        let x_strata = (samples_per_pixel as f64).sqrt() as u32;
+        let y_strata = samples_per_pixel.div_ceil(x_strata);
+
+        Self { samples_per_pixel, x_strata, y_strata, pixel_seed: 0, sample_index: 0, dimension: 0 }
+    }
+
+    fn stratum(&mut self, count: u32) -> Option<u32> {
+        // which of `count` strata the current sample falls in for the next dimension
+        let seed = hash(self.pixel_seed ^ hash(self.dimension as u64)) as u32;
+        self.dimension += 1;
+
+        if self.sample_index >= count {
+            return None;
+        }
+
+        Some(permutation_element(self.sample_index, count, seed))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, i: i32, j: i32, sample_index: i32) {
+        self.pixel_seed = pixel_hash(i, j);
+        self.sample_index = sample_index as u32;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        match self.stratum(self.samples_per_pixel) {
+            Some(s) => (s as f64 + random_f64()) / self.samples_per_pixel as f64,
+            None => random_f64(),
+        }
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        match self.stratum(self.x_strata * self.y_strata) {
+            Some(s) => (
+                ((s % self.x_strata) as f64 + random_f64()) / self.x_strata as f64,
+                ((s / self.x_strata) as f64 + random_f64()) / self.y_strata as f64,
+            ),
+            None => (random_f64(), random_f64()),
+        }
+    }
+}
+
+// the Halton sequence with a prime base per dimension. digits are scrambled with a per-pixel
+// random permutation so neighbouring pixels don't share the same sample pattern
+pub struct HaltonSampler {
+    pixel_seed: u64,
+    sample_index: u64,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new() -> Self {
+        Self { pixel_seed: 0, sample_index: 0, dimension: 0 }
+    }
+
+    fn sample_dimension(&mut self) -> f64 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        // past the table of bases the sequence is no better than independent samples
+        if dimension >= PRIMES.len() {
+            return random_f64();
+        }
+
+        let seed = hash(self.pixel_seed ^ hash(dimension as u64));
+        scrambled_radical_inverse(PRIMES[dimension], self.sample_index, seed)
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, i: i32, j: i32, sample_index: i32) {
+        self.pixel_seed = pixel_hash(i, j);
+        self.sample_index = sample_index as u64;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.sample_dimension()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.sample_dimension(), self.sample_dimension())
+    }
+}
+
+// Owen-scrambled Sobol points, padded: every pair of dimensions uses the first two Sobol
+// dimensions with its own scramble and its own shuffled sample order (Burley 2020, "Practical
+// Hash-based Owen Scrambling"), so any number of dimensions can be drawn
+pub struct SobolSampler {
+    pixel_seed: u64,
+    sample_index: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new() -> Self {
+        Self { pixel_seed: 0, sample_index: 0, dimension: 0 }
+    }
+
+    fn next_seed(&mut self) -> u32 {
+        let seed = hash(self.pixel_seed ^ hash(self.dimension)) as u32;
+        self.dimension += 1;
+        seed
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, i: i32, j: i32, sample_index: i32) {
+        self.pixel_seed = pixel_hash(i, j);
+        self.sample_index = sample_index as u32;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let seed = self.next_seed();
+        let index = nested_uniform_scramble(self.sample_index, seed);
+        let x = nested_uniform_scramble(sobol(index, 0), seed.wrapping_add(1));
+
+        to_unit_f64(x)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let seed = self.next_seed();
+        let index = nested_uniform_scramble(self.sample_index, seed);
+        let x = nested_uniform_scramble(sobol(index, 0), seed.wrapping_add(1));
+        let y = nested_uniform_scramble(sobol(index, 1), seed.wrapping_add(2));
+
+        (to_unit_f64(x), to_unit_f64(y))
+    }
+}
+
+fn to_unit_f64(x: u32) -> f64 {
+    (x as f64 / 4294967296.0).min(ONE_MINUS_EPSILON)
+}
+
+fn pixel_hash(i: i32, j: i32) -> u64 {
+    hash(((i as u32 as u64) << 32) | j as u32 as u64)
+}
+
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    // element i of a random permutation of 0..l selected by p, without building the permutation
+    // (Kensler 2013, "Correlated Multi-Jittered Sampling")
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+
+        if i < l {
+            break;
+        }
+    }
+
+    (i.wrapping_add(p)) % l
+}
+
+fn scrambled_radical_inverse(base: u64, mut a: u64, seed: u64) -> f64 {
+    // reflect the base-b digits of a about the radix point, passing each digit through its own
+    // random permutation. trailing zero digits are permuted too until they stop mattering
+    let inv_base = 1.0 / base as f64;
+    let mut inv_base_m = 1.0;
+    let mut result = 0.0;
+    let mut digit_index: u64 = 0;
+
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 {
+        let next = a / base;
+        let digit = a - next * base;
+        let digit_seed = hash(seed ^ hash(digit_index)) as u32;
+        let permuted = permutation_element(digit as u32, base as u32, digit_seed);
+
+        inv_base_m *= inv_base;
+        result += permuted as f64 * inv_base_m;
+        a = next;
+        digit_index += 1;
+    }
+
+    result.min(ONE_MINUS_EPSILON)
+}
+
+fn sobol(index: u32, dimension: usize) -> u32 {
+    // the first two dimensions of the Sobol sequence: the van der Corput sequence, and the
+    // sequence generated by the primitive polynomial x + 1
+    let mut direction: u32 = 1 << 31;
+    let mut x = 0;
+
+    for bit in 0..32 {
+        if (index >> bit) & 1 == 1 {
+            x ^= direction;
+        }
+
+        direction = match dimension {
+            0 => direction >> 1,
+            _ => direction ^ (direction >> 1),
+        };
+    }
+
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    // Owen scrambling in base 2 as a hash over the bit-reversed value
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x.reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXELS: i32 = 256;
+    const SAMPLES: i32 = 64;
+
+    type Integrand = fn(f64, f64) -> f64;
+
+    fn rmse(sampler_type: SamplerType, f: Integrand, expected: f64) -> f64 {
+        // estimate the integral of f over the unit square once per pixel. the second 2D dimension
+        // is used so that padded dimensions are covered, not just the pixel jitter
+        let mut sampler = sampler_type.create(SAMPLES);
+        let mut squared_error = 0.0;
+
+        for i in 0..PIXELS {
+            let mut sum = 0.0;
+            for s in 0..SAMPLES {
+                sampler.start_pixel_sample(i, 7, s);
+                let _ = sampler.get_2d();
+                let (u, v) = sampler.get_2d();
+                sum += f(u, v);
+            }
+
+            squared_error += (sum / SAMPLES as f64 - expected).powi(2);
+        }
+
+        (squared_error / PIXELS as f64).sqrt()
+    }
+
+    fn gaussian(x: f64, y: f64) -> f64 {
+        (-(x * x + y * y)).exp()
+    }
+
+    fn quarter_disk(x: f64, y: f64) -> f64 {
+        if x * x + y * y < 0.64 { 1.0 } else { 0.0 }
+    }
+
+    #[test]
+    fn samples_are_in_unit_square() {
+        for sampler_type in [SamplerType::Stratified, SamplerType::Halton, SamplerType::Sobol] {
+            let mut sampler = sampler_type.create(16);
+            for s in 0..64 {
+                sampler.start_pixel_sample(3, 5, s);
+                for _ in 0..40 {
+                    let (u, v) = sampler.get_2d();
+                    let w = sampler.get_1d();
+                    assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v) && (0.0..1.0).contains(&w));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn converges_faster_than_uniform() {
+        // integral of exp(-x^2 - y^2) over the unit square, and the area of a quarter disk of radius 0.8
+        let integrands: [(Integrand, f64); 2] =
+            [(gaussian, 0.557746285351034), (quarter_disk, 0.16 * crate::common::PI)];
+
+        for (f, expected) in integrands {
+            let uniform = rmse(SamplerType::Independent, f, expected);
+            for sampler_type in [SamplerType::Stratified, SamplerType::Halton, SamplerType::Sobol] {
+                let error = rmse(sampler_type, f, expected);
+                assert!(error < 0.5 * uniform, "{:?}: rmse {} vs uniform {}", sampler_type, error, uniform);
+            }
+        }
+    }
+}
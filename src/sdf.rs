@@ -0,0 +1,295 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{dot, unit_vector, Point3, Vec3};
+
+// how close to the surface sphere tracing has to get to count as a hit
+const SURFACE_DISTANCE: f64 = 1e-4;
+
+// most steps sphere tracing takes along a ray before giving up
+const MAX_MARCH_STEPS: i32 = 512;
+
+// a signed distance function: the distance from a point to the surface, negative inside. it may
+// underestimate the distance, which only costs extra steps, but never overestimate it
+pub trait Sdf {
+    fn distance(&self, p: Point3) -> f64;
+}
+
+pub struct SdfSphere {
+    center: Point3,
+    radius: f64,
+}
+
+impl SdfSphere {
+    pub fn new(center: Point3, radius: f64) -> Self {
+        Self { center, radius }
+    }
+}
+
+impl Sdf for SdfSphere {
+    fn distance(&self, p: Point3) -> f64 {
+        (p - self.center).length() - self.radius
+    }
+}
+
+// axis-aligned box with its edges rounded off by `rounding`, which is included in `half_size`
+pub struct SdfBox {
+    center: Point3,
+    half_size: Vec3,
+    rounding: f64,
+}
+
+impl SdfBox {
+    pub fn new(center: Point3, half_size: Vec3, rounding: f64) -> Self {
+        Self { center, half_size, rounding }
+    }
+}
+
+impl Sdf for SdfBox {
+    fn distance(&self, p: Point3) -> f64 {
+        let p = p - self.center;
+        let q = Vec3::new(
+            p.x().abs() - self.half_size.x() + self.rounding,
+            p.y().abs() - self.half_size.y() + self.rounding,
+            p.z().abs() - self.half_size.z() + self.rounding,
+        );
+
+        let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+        let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+        outside + inside - self.rounding
+    }
+}
+
+// torus lying flat, around the y axis
+pub struct SdfTorus {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl SdfTorus {
+    pub fn new(center: Point3, major_radius: f64, minor_radius: f64) -> Self {
+        Self { center, major_radius, minor_radius }
+    }
+}
+
+impl Sdf for SdfTorus {
+    fn distance(&self, p: Point3) -> f64 {
+        let p = p - self.center;
+        let ring = (p.x() * p.x() + p.z() * p.z()).sqrt() - self.major_radius;
+        (ring * ring + p.y() * p.y()).sqrt() - self.minor_radius
+    }
+}
+
+// the union of two shapes, blended together wherever they come within `k` of each other
+pub struct SmoothUnion {
+    a: Box<dyn Sdf>,
+    b: Box<dyn Sdf>,
+    k: f64,
+}
+
+impl SmoothUnion {
+    pub fn new(a: impl Sdf + 'static, b: impl Sdf + 'static, k: f64) -> Self {
+        Self { a: Box::new(a), b: Box::new(b), k }
+    }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Point3) -> f64 {
+        // polynomial smooth minimum
+        let (a, b) = (self.a.distance(p), self.b.distance(p));
+        let h = (0.5 + 0.5 * (b - a) / self.k).clamp(0.0, 1.0);
+        b + (a - b) * h - self.k * h * (1.0 - h)
+    }
+}
+
+// endless copies of a shape, one every `period` along each axis, or none along an axis with a
+// period of zero. the shape should fit inside the cell around the origin
+pub struct Repeat {
+    shape: Box<dyn Sdf>,
+    period: Vec3,
+}
+
+impl Repeat {
+    pub fn new(shape: impl Sdf + 'static, period: Vec3) -> Self {
+        Self { shape: Box::new(shape), period }
+    }
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: Point3) -> f64 {
+        let wrap = |x: f64, period: f64| if period > 0.0 { x - period * (x / period).round() } else { x };
+        self.shape.distance(Point3::new(wrap(p.x(), self.period.x()), wrap(p.y(), self.period.y()), wrap(p.z(), self.period.z())))
+    }
+}
+
+// a shape moved by `offset`, for operations like twisting that work around the origin
+pub struct Translate {
+    shape: Box<dyn Sdf>,
+    offset: Vec3,
+}
+
+impl Translate {
+    pub fn new(shape: impl Sdf + 'static, offset: Vec3) -> Self {
+        Self { shape: Box::new(shape), offset }
+    }
+}
+
+impl Sdf for Translate {
+    fn distance(&self, p: Point3) -> f64 {
+        self.shape.distance(p - self.offset)
+    }
+}
+
+// a shape twisted around the y axis by `rate` radians per unit of height
+pub struct Twist {
+    shape: Box<dyn Sdf>,
+    rate: f64,
+}
+
+impl Twist {
+    pub fn new(shape: impl Sdf + 'static, rate: f64) -> Self {
+        Self { shape: Box::new(shape), rate }
+    }
+}
+
+impl Sdf for Twist {
+    fn distance(&self, p: Point3) -> f64 {
+        let angle = self.rate * p.y();
+        let (sin, cos) = angle.sin_cos();
+        let twisted = Point3::new(cos * p.x() - sin * p.z(), p.y(), sin * p.x() + cos * p.z());
+
+        // twisting stretches space more the further it is from the axis, so the distance is
+        // scaled down to stay an underestimate
+        let stretch = (1.0 + (self.rate * (p.x() * p.x() + p.z() * p.z()).sqrt()).powi(2)).sqrt();
+        self.shape.distance(twisted) / stretch
+    }
+}
+
+// the mandelbulb fractal, fitting in a sphere of about `scale` around `center`
+pub struct Mandelbulb {
+    center: Point3,
+    scale: f64,
+    power: f64,
+    iterations: i32,
+}
+
+impl Mandelbulb {
+    pub fn new(center: Point3, scale: f64, power: f64, iterations: i32) -> Self {
+        Self { center, scale, power, iterations }
+    }
+}
+
+impl Sdf for Mandelbulb {
+    fn distance(&self, p: Point3) -> f64 {
+        // distance estimate from how quickly the iteration escapes
+        let c = (p - self.center) / self.scale;
+        let mut z = c;
+        let mut dr = 1.0;
+        let mut r = z.length();
+
+        for _ in 0..self.iterations {
+            if r > 2.0 {
+                break;
+            }
+
+            let theta = (z.z() / r).clamp(-1.0, 1.0).acos() * self.power;
+            let phi = z.y().atan2(z.x()) * self.power;
+            dr = self.power * r.powf(self.power - 1.0) * dr + 1.0;
+
+            let zr = r.powf(self.power);
+            z = zr * Vec3::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos()) + c;
+            r = z.length();
+        }
+
+        if r < 1e-12 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr * self.scale
+    }
+}
+
+// a surface found by sphere tracing a signed distance function. the distance function says
+// nothing about how big the shape is, so it's only traced within `bbox`
+pub struct SdfObject {
+    sdf: Box<dyn Sdf>,
+    bbox: Aabb,
+    mat: Box<dyn Material>,
+}
+
+impl SdfObject {
+    pub fn new(sdf: impl Sdf + 'static, bbox: Aabb, mat: impl Material + 'static) -> Self {
+        Self { sdf: Box::new(sdf), bbox, mat: Box::new(mat) }
+    }
+
+    fn gradient(&self, p: Point3) -> Vec3 {
+        // central differences on the corners of a tetrahedron, four evaluations instead of six
+        let h = 0.5 * SURFACE_DISTANCE;
+        let corners = [Vec3::new(1.0, -1.0, -1.0), Vec3::new(-1.0, -1.0, 1.0), Vec3::new(-1.0, 1.0, -1.0), Vec3::new(1.0, 1.0, 1.0)];
+
+        let mut gradient = Vec3::new(0.0, 0.0, 0.0);
+        for k in corners {
+            gradient = gradient + self.sdf.distance(p + h * k) * k;
+        }
+        gradient
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let ray_t = self.bbox.clip(r, ray_t)?;
+        let length = r.direction().length();
+
+        // rays leaving the surface, after refracting into it for example, start right on it.
+        // which side they're heading into decides whether to look for the surface from outside
+        // or from inside
+        let start = r.at(ray_t.min);
+        let distance = self.sdf.distance(start);
+        let side = if distance.abs() > SURFACE_DISTANCE {
+            distance.signum()
+        } else {
+            dot(&self.gradient(start), &r.direction()).signum()
+        };
+
+        // step as far as the distance function says is empty, until that gets small enough
+        let mut t = ray_t.min;
+        let mut steps = 0;
+        loop {
+            let distance = side * self.sdf.distance(r.at(t));
+            let travelled = (t - ray_t.min) * length;
+            if distance < SURFACE_DISTANCE && travelled > 4.0 * SURFACE_DISTANCE {
+                break;
+            }
+
+            t += distance.max(SURFACE_DISTANCE) / length;
+            steps += 1;
+            if t >= ray_t.max || steps == MAX_MARCH_STEPS {
+                return None;
+            }
+        }
+
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = r.at(t);
+        let outward_normal = unit_vector(self.gradient(rec.p));
+        rec.set_face_normal(r, &outward_normal);
+
+        // there are no texture coordinates, so any tangent frame will do
+        let frame = Onb::new(outward_normal);
+        (rec.dpdu, rec.dpdv) = (frame.to_world(Vec3::new(1.0, 0.0, 0.0)), frame.to_world(Vec3::new(0.0, 1.0, 0.0)));
+        rec.mat = Some(&(*self.mat));
+
+        if !rec.is_opaque(r) {
+            return None;
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
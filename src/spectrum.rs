@@ -0,0 +1,248 @@
+use std::ops::{Add, Mul};
+use std::sync::OnceLock;
+
+use crate::vector::{cross, dot, Color, Vec3};
+
+// range of wavelengths traced in spectral mode, in nanometers
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+
+// wavelengths carried by each path
+pub const N_SPECTRUM_SAMPLES: usize = 4;
+
+// a handful of wavelengths traced together along one path. the first, the hero wavelength, is
+// drawn uniformly and the rest are spaced evenly after it, wrapping around the range, so
+// together they cover the spectrum with a single random number (Wilkie et al. 2014)
+#[derive(Copy, Clone)]
+pub struct SampledWavelengths {
+    lambda: [f64; N_SPECTRUM_SAMPLES],
+    secondary_terminated: bool,
+}
+
+impl SampledWavelengths {
+    pub fn sample_uniform(u: f64) -> Self {
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+        let mut lambda = [0.0; N_SPECTRUM_SAMPLES];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            let offset = u * range + i as f64 * range / N_SPECTRUM_SAMPLES as f64;
+            *l = LAMBDA_MIN + offset % range;
+        }
+
+        Self { lambda, secondary_terminated: false }
+    }
+
+    pub fn hero(&self) -> f64 {
+        self.lambda[0]
+    }
+
+    pub fn get(&self, i: usize) -> f64 {
+        self.lambda[i]
+    }
+
+    pub fn terminate_secondary(&mut self) {
+        // after something that sends each wavelength its own way, like refraction through
+        // dispersive glass, only the hero wavelength's path is still valid
+        self.secondary_terminated = true;
+    }
+
+    pub fn to_rgb(self, radiance: SampledSpectrum) -> Color {
+        // monte carlo estimate of the color the radiance is seen as, for wavelengths drawn
+        // uniformly over the range
+        let calibration = calibration();
+        let count = if self.secondary_terminated { 1 } else { N_SPECTRUM_SAMPLES };
+
+        let mut rgb = Color::new(0.0, 0.0, 0.0);
+        for i in 0..count {
+            rgb = rgb + radiance.values[i] * calibration.rgb_response(self.lambda[i]);
+        }
+
+        (LAMBDA_MAX - LAMBDA_MIN) / count as f64 * rgb
+    }
+}
+
+// a spectral quantity at each of the path's wavelengths
+#[derive(Copy, Clone)]
+pub struct SampledSpectrum {
+    pub values: [f64; N_SPECTRUM_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn constant(c: f64) -> Self {
+        Self { values: [c; N_SPECTRUM_SAMPLES] }
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = Self;
+
+    fn add(self, s: Self) -> Self {
+        let mut values = self.values;
+        for (v, w) in values.iter_mut().zip(s.values) {
+            *v += w;
+        }
+        Self { values }
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(self, s: Self) -> Self {
+        let mut values = self.values;
+        for (v, w) in values.iter_mut().zip(s.values) {
+            *v *= w;
+        }
+        Self { values }
+    }
+}
+
+pub fn uplift(rgb: Color, lambda: &SampledWavelengths) -> SampledSpectrum {
+    // a smooth spectrum that is seen as exactly `rgb`, evaluated at the path's wavelengths. a
+    // gray is a flat spectrum, and saturated colors are clipped where the spectrum would go
+    // negative
+    let c = calibration().coefficients(rgb);
+
+    let mut values = [0.0; N_SPECTRUM_SAMPLES];
+    for (i, v) in values.iter_mut().enumerate() {
+        *v = dot(&c, &basis(lambda.get(i))).max(0.0);
+    }
+
+    SampledSpectrum { values }
+}
+
+// index of refraction, constant or varying with wavelength
+#[derive(Copy, Clone)]
+pub enum Ior {
+    Constant(f64),
+    // n = a + b / lambda^2, with lambda in micrometers
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum of b lambda^2 / (lambda^2 - c), with lambda in micrometers
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Ior {
+    pub fn water() -> Self {
+        Self::Cauchy { a: 1.3141, b: 0.0065 }
+    }
+
+    pub fn bk7() -> Self {
+        // common optical crown glass
+        Self::Sellmeier { b: [1.03961212, 0.231792344, 1.01046945], c: [0.00600069867, 0.0200179144, 103.560653] }
+    }
+
+    pub fn dense_flint() -> Self {
+        // SF11, a strongly dispersive glass for prisms
+        Self::Sellmeier { b: [1.73759695, 0.313747346, 1.89878101], c: [0.013188707, 0.0623068142, 155.23629] }
+    }
+
+    pub fn diamond() -> Self {
+        Self::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030625, 0.011236, 0.0] }
+    }
+
+    pub fn at(&self, lambda: f64) -> f64 {
+        let l2 = (lambda / 1000.0).powi(2);
+
+        match self {
+            Self::Constant(n) => *n,
+            Self::Cauchy { a, b } => a + b / l2,
+            Self::Sellmeier { b, c } => (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>()).sqrt(),
+        }
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        !matches!(self, Self::Constant(_))
+    }
+}
+
+struct Calibration {
+    // linear sRGB seen for each wavelength, balanced so a flat spectrum is white
+    white: Color,
+    // solves for the basis weights that produce a given color
+    inverse: [Vec3; 3],
+}
+
+impl Calibration {
+    fn new() -> Self {
+        // integrate the color matching functions against a flat spectrum and against each basis
+        // function, at one nanometer intervals
+        let mut white = Color::new(0.0, 0.0, 0.0);
+        let mut columns = [Color::new(0.0, 0.0, 0.0); 3];
+        let mut lambda = LAMBDA_MIN;
+        while lambda <= LAMBDA_MAX {
+            let rgb = xyz_to_rgb(cie_xyz(lambda));
+            let b = basis(lambda);
+            white = white + rgb;
+            for (i, column) in columns.iter_mut().enumerate() {
+                *column = *column + b[i] * rgb;
+            }
+            lambda += 1.0;
+        }
+
+        let balance = |c: Color| Color::new(c.x() / white.x(), c.y() / white.y(), c.z() / white.z());
+        let columns = columns.map(balance);
+        Self { white, inverse: invert(columns) }
+    }
+
+    fn rgb_response(&self, lambda: f64) -> Color {
+        let rgb = xyz_to_rgb(cie_xyz(lambda));
+        Color::new(rgb.x() / self.white.x(), rgb.y() / self.white.y(), rgb.z() / self.white.z())
+    }
+
+    fn coefficients(&self, rgb: Color) -> Vec3 {
+        self.inverse[0] * rgb.x() + self.inverse[1] * rgb.y() + self.inverse[2] * rgb.z()
+    }
+}
+
+fn calibration() -> &'static Calibration {
+    static CALIBRATION: OnceLock<Calibration> = OnceLock::new();
+    CALIBRATION.get_or_init(Calibration::new)
+}
+
+fn basis(lambda: f64) -> Vec3 {
+    // smooth red, green and blue spectra that always add up to one, so any mix of equal
+    // amounts is flat
+    let step = |center: f64| 1.0 / (1.0 + (-(lambda - center) / 12.0).exp());
+    let red = step(590.0);
+    let blue = 1.0 - step(490.0);
+    Vec3::new(red, 1.0 - red - blue, blue)
+}
+
+fn cie_xyz(lambda: f64) -> Vec3 {
+    // CIE 1931 color matching functions, as a sum of piecewise gaussians
+    // (Wyman et al. 2013, "Simple Analytic Approximations to the CIE XYZ Color Matching Functions")
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let sigma = if lambda < mu { sigma_below } else { sigma_above };
+        (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+    };
+
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+fn xyz_to_rgb(xyz: Vec3) -> Color {
+    // to linear sRGB
+    Color::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
+
+fn invert(columns: [Vec3; 3]) -> [Vec3; 3] {
+    // inverse of the matrix with the given columns, also as columns
+    let [a, b, c] = columns;
+    let r0 = cross(b, c);
+    let r1 = cross(c, a);
+    let r2 = cross(a, b);
+    let inv_det = 1.0 / dot(&a, &r0);
+
+    // the cross products are the rows of the inverse; transpose them into columns
+    [
+        Vec3::new(r0.x(), r1.x(), r2.x()) * inv_det,
+        Vec3::new(r0.y(), r1.y(), r2.y()) * inv_det,
+        Vec3::new(r0.z(), r1.z(), r2.z()) * inv_det,
+    ]
+}
@@ -1,6 +1,8 @@
+use crate::aabb::Aabb;
+use crate::common::PI;
 use crate::hittable::{Hittable, HitRecord};
 use crate::material::Material;
-use crate::vector::{self, Point3};
+use crate::vector::{self, Point3, Vec3};
 use crate::ray::Ray;
 use crate::interval::Interval;
 
@@ -14,6 +16,30 @@ impl Sphere {
     pub fn new(center: Point3, radius: f64, mat: impl Material + 'static) -> Self {
         Self{center, radius, mat: Box::new(mat)}
     }
+
+    pub fn get_sphere_uv(p: &Point3) -> (f64, f64) {
+        // p: a given point on the sphere of radius one, centered at the origin
+        // u: returned value [0,1] of angle around the Y axis from X=-1
+        // v: returned value [0,1] of angle from Y=-1 to Y=+1
+        let theta = (-p.y()).acos();
+        let phi = (-p.z()).atan2(p.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    pub fn get_sphere_tangents(p: &Point3) -> (Vec3, Vec3) {
+        // p: a given point on the sphere, relative to its center
+        // returns the derivatives of p with respect to the u and v of get_sphere_uv
+        let ring_radius = (p.x().powi(2) + p.z().powi(2)).sqrt();
+        if ring_radius < 1e-9 {
+            // at the poles u is degenerate; any tangent frame will do
+            return (Vec3::new(0.0, 0.0, -2.0 * PI), Vec3::new(PI * p.y().abs(), 0.0, 0.0));
+        }
+
+        let dpdu = 2.0 * PI * Vec3::new(p.z(), 0.0, -p.x());
+        let dpdv = PI * Vec3::new(-p.y() * p.x() / ring_radius, ring_radius, -p.y() * p.z() / ring_radius);
+        (dpdu, dpdv)
+    }
 }
 
 impl Hittable for Sphere {
@@ -31,27 +57,36 @@ impl Hittable for Sphere {
 
         let sqrtd = discriminant.sqrt();
 
-        // find the nearest root that lies in the acceptable range
-        let mut root = (h - sqrtd)  / a;
-        if !ray_t.surrounds(root) {
-            root = (h + sqrtd) / a;
+        // find the nearest root that lies in the acceptable range, and that a cutout
+        // material doesn't let the ray through
+        for root in [(h - sqrtd) / a, (h + sqrtd) / a] {
+            if !ray_t.surrounds(root) {
+                continue;
+            }
+
+            let mut rec = HitRecord::new();
+            rec.t = root;
+            rec.p = r.at(rec.t);
+            // rec.normal = (rec.p - self.center) / self.radius;
+            let outward_normal = (rec.p - self.center) / self.radius;
+            rec.set_face_normal(r, &outward_normal);
+            (rec.u, rec.v) = Self::get_sphere_uv(&outward_normal);
+            (rec.dpdu, rec.dpdv) = Self::get_sphere_tangents(&(rec.p - self.center));
+            // rec.mat = self.mat;
+            // rec.update_mat(*self.mat);
+            // rec.mat = Box::new(&self.mat);
+            rec.mat = Some(&(*self.mat));
 
-            if !ray_t.surrounds(root){
-                return None;
+            if rec.is_opaque(r) {
+                return Some(rec);
             }
         }
 
-        let mut rec = HitRecord::new();
-        rec.t = root;
-        rec.p = r.at(rec.t);
-        // rec.normal = (rec.p - self.center) / self.radius;
-        let outward_normal = (rec.p - self.center) / self.radius;
-        rec.set_face_normal(r, &outward_normal);
-        // rec.mat = self.mat;
-        // rec.update_mat(*self.mat);
-        // rec.mat = Box::new(&self.mat);
-        rec.mat = Some(&(*self.mat));
-
-        return Some(rec);
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::from_points(self.center - rvec, self.center + rvec)
     }
 }
\ No newline at end of file
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+
+use crate::aabb::Aabb;
+use crate::common::PI;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::mesh::{edge_key, Mesh, TriangleMesh};
+use crate::ray::Ray;
+use crate::vector::Point3;
+
+#[derive(Copy, Clone)]
+pub enum SubdivisionScheme {
+    // for quad meshes; other polygons become quads after the first level
+    CatmullClark,
+    // for triangle meshes; other polygons are split into triangles first
+    Loop,
+}
+
+// a smooth surface approximating a control cage, by subdividing it `levels` times and then
+// tracing the triangles. every level quadruples the number of faces
+pub struct SubdivisionSurface {
+    mesh: TriangleMesh,
+}
+
+impl SubdivisionSurface {
+    pub fn new(cage: &Mesh, scheme: SubdivisionScheme, levels: u32, mat: impl Material + 'static) -> Self {
+        let subdivide = match scheme {
+            SubdivisionScheme::CatmullClark => catmull_clark,
+            SubdivisionScheme::Loop => loop_subdivide,
+        };
+
+        let mut mesh = cage.clone();
+        for _ in 0..levels {
+            mesh = subdivide(&mesh);
+        }
+
+        Self { mesh: TriangleMesh::new(&mesh, mat) }
+    }
+}
+
+impl Hittable for SubdivisionSurface {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        self.mesh.hit(r, ray_t)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.mesh.bounding_box()
+    }
+}
+
+// each new vertex is a weighted sum of the old ones, worked out once and then applied to both the
+// positions and the texture coordinates
+type Stencil = Vec<(usize, f64)>;
+
+fn blend(a: Stencil, b: Stencil, t: f64) -> Stencil {
+    let mut stencil: Stencil = a.into_iter().map(|(i, w)| (i, (1.0 - t) * w)).collect();
+    stencil.extend(b.into_iter().map(|(i, w)| (i, t * w)));
+    stencil
+}
+
+struct Topology {
+    edges: Vec<(usize, usize)>,
+    edge_index: HashMap<(usize, usize), usize>,
+    edge_faces: Vec<Vec<usize>>,
+    vertex_edges: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(mesh: &Mesh) -> Self {
+        let mut topology = Self {
+            edges: Vec::new(),
+            edge_index: HashMap::new(),
+            edge_faces: Vec::new(),
+            vertex_edges: vec![Vec::new(); mesh.positions.len()],
+            vertex_faces: vec![Vec::new(); mesh.positions.len()],
+        };
+
+        for (f, face) in mesh.faces.iter().enumerate() {
+            for (k, &a) in face.iter().enumerate() {
+                let b = face[(k + 1) % face.len()];
+                let key = edge_key(a, b);
+                let e = *topology.edge_index.entry(key).or_insert_with(|| {
+                    topology.edges.push(key);
+                    topology.edge_faces.push(Vec::new());
+                    topology.vertex_edges[a].push(topology.edges.len() - 1);
+                    topology.vertex_edges[b].push(topology.edges.len() - 1);
+                    topology.edges.len() - 1
+                });
+                topology.edge_faces[e].push(f);
+                topology.vertex_faces[a].push(f);
+            }
+        }
+
+        topology
+    }
+
+    fn sharpness(&self, mesh: &Mesh, e: usize) -> f64 {
+        // the mesh's boundary, and any edge not shared by exactly two faces, is infinitely sharp
+        let (a, b) = self.edges[e];
+        if self.edge_faces[e].len() != 2 {
+            return f64::INFINITY;
+        }
+        mesh.sharpness(a, b)
+    }
+
+    fn other_end(&self, e: usize, v: usize) -> usize {
+        let (a, b) = self.edges[e];
+        if a == v {
+            b
+        } else {
+            a
+        }
+    }
+
+    fn edge_stencil(&self, mesh: &Mesh, e: usize, smooth: Stencil) -> Stencil {
+        // sharp edges stay straight, splitting at their midpoint, and ones with a sharpness
+        // between 0 and 1 fall somewhere in between
+        let (a, b) = self.edges[e];
+        let sharp = vec![(a, 0.5), (b, 0.5)];
+        let sharpness = self.sharpness(mesh, e);
+        if sharpness >= 1.0 {
+            sharp
+        } else if sharpness > 0.0 {
+            blend(smooth, sharp, sharpness)
+        } else {
+            smooth
+        }
+    }
+
+    fn vertex_stencil(&self, mesh: &Mesh, v: usize, smooth: Stencil) -> Stencil {
+        // a vertex on two sharp edges slides along the crease, and one on more stays put as a
+        // corner. its sharpness is the average of its sharp edges'
+        let sharp_edges: Vec<usize> = self.vertex_edges[v].iter().copied().filter(|&e| self.sharpness(mesh, e) > 0.0).collect();
+        if sharp_edges.len() < 2 {
+            return smooth;
+        }
+
+        let sharp = if sharp_edges.len() == 2 {
+            vec![(v, 0.75), (self.other_end(sharp_edges[0], v), 0.125), (self.other_end(sharp_edges[1], v), 0.125)]
+        } else {
+            vec![(v, 1.0)]
+        };
+
+        let sharpness = sharp_edges.iter().map(|&e| self.sharpness(mesh, e)).sum::<f64>() / sharp_edges.len() as f64;
+        if sharpness >= 1.0 {
+            sharp
+        } else {
+            blend(smooth, sharp, sharpness)
+        }
+    }
+
+    fn child_creases(&self, mesh: &Mesh, edge_points: usize) -> HashMap<(usize, usize), f64> {
+        // both halves of a split edge are one level less sharp. edge e's new point is vertex
+        // edge_points + e, and old vertices keep their indices
+        let mut creases = HashMap::new();
+        for (e, &(a, b)) in self.edges.iter().enumerate() {
+            let sharpness = mesh.sharpness(a, b) - 1.0;
+            if sharpness > 0.0 {
+                creases.insert(edge_key(a, edge_points + e), sharpness);
+                creases.insert(edge_key(edge_points + e, b), sharpness);
+            }
+        }
+        creases
+    }
+}
+
+fn apply(mesh: &Mesh, stencils: &[Stencil], faces: Vec<Vec<usize>>, creases: HashMap<(usize, usize), f64>) -> Mesh {
+    let positions = stencils
+        .iter()
+        .map(|stencil| stencil.iter().fold(Point3::new(0.0, 0.0, 0.0), |sum, &(i, w)| sum + w * mesh.positions[i]))
+        .collect();
+
+    let uvs = if mesh.uvs.is_empty() {
+        Vec::new()
+    } else {
+        stencils
+            .iter()
+            .map(|stencil| stencil.iter().fold((0.0, 0.0), |sum, &(i, w)| (sum.0 + w * mesh.uvs[i].0, sum.1 + w * mesh.uvs[i].1)))
+            .collect()
+    };
+
+    Mesh { positions, uvs, faces, creases }
+}
+
+fn catmull_clark(mesh: &Mesh) -> Mesh {
+    // one level of catmull-clark subdivision, with the semi-sharp creases of DeRose et al. the new
+    // vertices are the moved old vertices, then a point on each edge, then one in each face
+    let topology = Topology::new(mesh);
+    let (vertex_count, edge_count) = (mesh.positions.len(), topology.edges.len());
+
+    let face_stencil = |f: usize| -> Stencil {
+        let face = &mesh.faces[f];
+        face.iter().map(|&i| (i, 1.0 / face.len() as f64)).collect()
+    };
+
+    let mut stencils = Vec::with_capacity(vertex_count + edge_count + mesh.faces.len());
+    for v in 0..vertex_count {
+        // (Q + 2R + (n - 3) v) / n, with Q the average of the face points around the vertex and R
+        // the average of the edge midpoints. stray vertices outside any face stay where they are
+        let n = topology.vertex_edges[v].len() as f64;
+        if n == 0.0 {
+            stencils.push(vec![(v, 1.0)]);
+            continue;
+        }
+        let mut smooth = vec![(v, (n - 3.0) / n)];
+        let faces = &topology.vertex_faces[v];
+        for &f in faces {
+            smooth.extend(face_stencil(f).into_iter().map(|(i, w)| (i, w / (faces.len() as f64 * n))));
+        }
+        for &e in &topology.vertex_edges[v] {
+            let (a, b) = topology.edges[e];
+            smooth.extend([(a, 1.0 / (n * n)), (b, 1.0 / (n * n))]);
+        }
+        stencils.push(topology.vertex_stencil(mesh, v, smooth));
+    }
+
+    for (e, &(a, b)) in topology.edges.iter().enumerate() {
+        // the average of the edge's ends and the face points on either side
+        let mut smooth = vec![(a, 0.25), (b, 0.25)];
+        for &f in &topology.edge_faces[e] {
+            smooth.extend(face_stencil(f).into_iter().map(|(i, w)| (i, 0.25 * w)));
+        }
+        stencils.push(topology.edge_stencil(mesh, e, smooth));
+    }
+
+    stencils.extend((0..mesh.faces.len()).map(face_stencil));
+
+    // a quad for every corner of every face, between the face point and the two edge points
+    let edge_point = |a: usize, b: usize| vertex_count + topology.edge_index[&edge_key(a, b)];
+    let mut faces = Vec::new();
+    for (f, face) in mesh.faces.iter().enumerate() {
+        let face_point = vertex_count + edge_count + f;
+        for (k, &v) in face.iter().enumerate() {
+            let next = face[(k + 1) % face.len()];
+            let previous = face[(k + face.len() - 1) % face.len()];
+            faces.push(vec![v, edge_point(v, next), face_point, edge_point(previous, v)]);
+        }
+    }
+
+    apply(mesh, &stencils, faces, topology.child_creases(mesh, vertex_count))
+}
+
+fn loop_subdivide(mesh: &Mesh) -> Mesh {
+    // one level of loop subdivision, with the same creases as catmull-clark. the new vertices are
+    // the moved old vertices, then a point on each edge
+    let triangles: Vec<Vec<usize>> = mesh
+        .faces
+        .iter()
+        .flat_map(|face| (1..face.len() - 1).map(move |k| vec![face[0], face[k], face[k + 1]]))
+        .collect();
+    let mesh = &Mesh { faces: triangles, ..mesh.clone() };
+
+    let topology = Topology::new(mesh);
+    let vertex_count = mesh.positions.len();
+
+    let mut stencils = Vec::with_capacity(vertex_count + topology.edges.len());
+    for v in 0..vertex_count {
+        // the vertex pulled towards its neighbours, with loop's weight for its valence
+        let n = topology.vertex_edges[v].len() as f64;
+        if n == 0.0 {
+            stencils.push(vec![(v, 1.0)]);
+            continue;
+        }
+        let beta = (0.625 - (0.375 + 0.25 * (2.0 * PI / n).cos()).powi(2)) / n;
+        let mut smooth = vec![(v, 1.0 - n * beta)];
+        smooth.extend(topology.vertex_edges[v].iter().map(|&e| (topology.other_end(e, v), beta)));
+        stencils.push(topology.vertex_stencil(mesh, v, smooth));
+    }
+
+    for (e, &(a, b)) in topology.edges.iter().enumerate() {
+        // 3/8 of each end and 1/8 of the vertices opposite the edge
+        let mut smooth = vec![(a, 0.375), (b, 0.375)];
+        for &f in &topology.edge_faces[e] {
+            let opposite = mesh.faces[f].iter().copied().find(|&i| i != a && i != b);
+            smooth.extend(opposite.map(|i| (i, 0.125)));
+        }
+        stencils.push(topology.edge_stencil(mesh, e, smooth));
+    }
+
+    // every triangle splits into four
+    let edge_point = |a: usize, b: usize| vertex_count + topology.edge_index[&edge_key(a, b)];
+    let mut faces = Vec::new();
+    for face in &mesh.faces {
+        let (a, b, c) = (face[0], face[1], face[2]);
+        let (ab, bc, ca) = (edge_point(a, b), edge_point(b, c), edge_point(c, a));
+        faces.extend([vec![a, ab, ca], vec![b, bc, ab], vec![c, ca, bc], vec![ab, bc, ca]]);
+    }
+
+    apply(mesh, &stencils, faces, topology.child_creases(mesh, vertex_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube() -> Mesh {
+        let positions = (0..8)
+            .map(|i| Point3::new(if i & 1 == 0 { -1.0 } else { 1.0 }, if i & 2 == 0 { -1.0 } else { 1.0 }, if i & 4 == 0 { -1.0 } else { 1.0 }))
+            .collect();
+        let faces = vec![
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+        ];
+        Mesh::new(positions, faces)
+    }
+
+    fn tetrahedron() -> Mesh {
+        let positions = vec![
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(1.0, -1.0, -1.0),
+            Point3::new(-1.0, 1.0, -1.0),
+            Point3::new(-1.0, -1.0, 1.0),
+        ];
+        Mesh::new(positions, vec![vec![0, 1, 2], vec![0, 3, 1], vec![0, 2, 3], vec![1, 3, 2]])
+    }
+
+    fn close(a: Point3, b: Point3) -> bool {
+        (a - b).length() < 1e-12
+    }
+
+    #[test]
+    fn catmull_clark_cube_counts_and_corners() {
+        // a vertex for each old vertex, edge and face, and a quad for each face corner
+        let mesh = catmull_clark(&cube());
+        assert_eq!(mesh.positions.len(), 8 + 12 + 6);
+        assert_eq!(mesh.faces.len(), 24);
+        assert!(mesh.faces.iter().all(|face| face.len() == 4));
+
+        // every corner of a smooth cube is pulled in to 5/9 on each axis
+        assert!(close(mesh.positions[7], Point3::new(5.0, 5.0, 5.0) / 9.0));
+        assert!(close(mesh.positions[0], Point3::new(-5.0, -5.0, -5.0) / 9.0));
+    }
+
+    #[test]
+    fn loop_tetrahedron_counts_and_vertex_rule() {
+        let mesh = loop_subdivide(&tetrahedron());
+        assert_eq!(mesh.positions.len(), 4 + 6);
+        assert_eq!(mesh.faces.len(), 16);
+        assert!(mesh.faces.iter().all(|face| face.len() == 3));
+
+        // valence 3 weighs each neighbour 3/16, and an edge point is 3/8 of each end and 1/8 of
+        // the two opposite vertices
+        let old = tetrahedron().positions;
+        let moved = (1.0 - 9.0 / 16.0) * old[0] + 3.0 / 16.0 * (old[1] + old[2] + old[3]);
+        assert!(close(mesh.positions[0], moved));
+        let edge = mesh.positions.iter().skip(4).any(|&p| close(p, 0.375 * (old[0] + old[1]) + 0.125 * (old[2] + old[3])));
+        assert!(edge, "no edge point for the edge from vertex 0 to 1");
+    }
+
+    #[test]
+    fn boundaries_follow_the_crease_rules() {
+        // an open quad: its edges are all boundary, so edge points are midpoints and each corner,
+        // with two boundary edges, moves by the crease rule
+        let quad = Mesh::new(
+            vec![Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0), Point3::new(2.0, 2.0, 0.0), Point3::new(0.0, 2.0, 0.0)],
+            vec![vec![0, 1, 2, 3]],
+        );
+        let mesh = catmull_clark(&quad);
+        assert_eq!(mesh.positions.len(), 4 + 4 + 1);
+        assert_eq!(mesh.faces.len(), 4);
+
+        let p = &quad.positions;
+        assert!(close(mesh.positions[0], 0.75 * p[0] + 0.125 * (p[1] + p[3])));
+        for (e, &(a, b)) in Topology::new(&quad).edges.iter().enumerate() {
+            assert!(close(mesh.positions[4 + e], 0.5 * (p[a] + p[b])));
+        }
+        assert!(close(mesh.positions[8], Point3::new(1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn infinitely_sharp_edges_stay_straight() {
+        // with every edge infinitely sharp, corners stay put and edges split at their midpoints,
+        // so the surface stays exactly on the cube however many levels there are
+        let mut cage = cube();
+        for (a, b) in Topology::new(&cage).edges {
+            cage.set_crease(a, b, f64::INFINITY);
+        }
+
+        let mut mesh = cage.clone();
+        for _ in 0..3 {
+            mesh = catmull_clark(&mesh);
+        }
+        assert_eq!(mesh.faces.len(), 6 * 64);
+
+        for p in &mesh.positions {
+            let extent = p.x().abs().max(p.y().abs()).max(p.z().abs());
+            assert!((extent - 1.0).abs() < 1e-12, "{:?} is off the cube", (p.x(), p.y(), p.z()));
+        }
+
+        // the points on one of the cube's edges are evenly spaced along it
+        let mut along: Vec<f64> = mesh.positions.iter().filter(|p| p.y() == -1.0 && p.z() == -1.0).map(|p| p.x()).collect();
+        along.sort_by(f64::total_cmp);
+        assert_eq!(along.len(), 9);
+        for (i, x) in along.iter().enumerate() {
+            assert!((x - (-1.0 + 0.25 * i as f64)).abs() < 1e-12);
+        }
+
+        // a softened crease, on the other hand, is pulled in
+        let mut soft = cube();
+        soft.set_crease(0, 1, 0.5);
+        let midpoint = catmull_clark(&soft).positions[8 + Topology::new(&soft).edge_index[&edge_key(0, 1)]];
+        assert!(midpoint.y() > -1.0 && midpoint.z() > -1.0);
+    }
+}
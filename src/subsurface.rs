@@ -0,0 +1,155 @@
+use crate::aabb::Aabb;
+use crate::common::INFINITY;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::{sample_interface, HenyeyGreenstein, Material};
+use crate::microfacet::TrowbridgeReitz;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vector::{dot, unit_vector, Color};
+
+// most scattering events followed inside a subsurface object before the path is given up on
+const MAX_WALK_STEPS: i32 = 1024;
+
+// translucent materials like skin, marble and wax, as a random walk through a scattering medium
+// filling a closed boundary shape, behind a rough dielectric surface. `albedo` is the color the
+// object ends up looking and `mean_free_path` how far light gets between scattering events, per
+// color channel; the longer it is, the further light bleeds through the object. the walk only
+// sees the boundary, so nothing else should be placed inside it
+pub struct Subsurface {
+    boundary: Box<dyn Hittable>,
+    refraction_index: f64,
+    distribution: TrowbridgeReitz,
+    sigma_t: Color,
+    sigma_s: Color,
+    phase_function: HenyeyGreenstein,
+}
+
+impl Subsurface {
+    pub fn new(
+        boundary: impl Hittable + 'static,
+        refraction_index: f64,
+        roughness: f64,
+        albedo: Color,
+        mean_free_path: Color,
+        g: f64,
+    ) -> Self {
+        let sigma_t = Color::new(1.0 / mean_free_path.x(), 1.0 / mean_free_path.y(), 1.0 / mean_free_path.z());
+        let single_scattering_albedo = Color::new(
+            single_scattering_albedo(albedo.x()),
+            single_scattering_albedo(albedo.y()),
+            single_scattering_albedo(albedo.z()),
+        );
+        let alpha = TrowbridgeReitz::roughness_to_alpha(roughness);
+
+        Self {
+            boundary: Box::new(boundary),
+            refraction_index,
+            distribution: TrowbridgeReitz::new(alpha, alpha),
+            sigma_t,
+            sigma_s: single_scattering_albedo * sigma_t,
+            phase_function: HenyeyGreenstein::new(Color::new(1.0, 1.0, 1.0), g),
+        }
+    }
+
+    fn random_walk(&self, mut ray: Ray, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        // follows light from where it entered until it leaves the surface again. each step's
+        // distance is drawn for one color channel, picked in proportion to the path's throughput
+        // in that channel, and every channel is weighted by how likely the step was over all
+        // three. that keeps the throughput from ever growing, however different the channels are
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+
+        for _ in 0..MAX_WALK_STEPS {
+            let exit = self.boundary.hit(&ray, Interval::new(0.001, INFINITY))?;
+
+            let total = throughput.x() + throughput.y() + throughput.z();
+            if total <= 0.0 {
+                return None;
+            }
+            let probability = throughput / total;
+
+            let u = sampler.get_1d() * total;
+            let channel = if u < throughput.x() { 0 } else if u < throughput.x() + throughput.y() { 1 } else { 2 };
+            let distance = -(1.0 - sampler.get_1d()).ln() / self.sigma_t[channel];
+            let ray_length = ray.direction().length();
+
+            if distance < exit.t * ray_length {
+                // scatter inside the medium
+                let transmittance = transmittance(self.sigma_t, distance);
+                let pdf = dot(&probability, &(self.sigma_t * transmittance));
+                throughput = throughput * self.sigma_s * transmittance / pdf;
+
+                let mut record = HitRecord::new();
+                record.p = ray.at(distance / ray_length);
+                let (_, scattered) = self.phase_function.scatter(ray, &record, sampler)?;
+                ray = scattered;
+                continue;
+            }
+
+            // reach the surface, then leave through it or reflect back inside
+            let transmittance = transmittance(self.sigma_t, exit.t * ray_length);
+            let pdf = dot(&probability, &transmittance);
+            throughput = throughput * transmittance / pdf;
+
+            let frame = Onb::new(exit.normal);
+            let wo = frame.to_local(-unit_vector(ray.direction()));
+            let (weight, wi) = sample_interface(&self.distribution, wo, 1.0 / self.refraction_index, sampler)?;
+            throughput = weight * throughput;
+
+            ray = Ray::new(exit.p, frame.to_world(wi));
+            if wi.z() < 0.0 {
+                return Some((throughput, ray));
+            }
+        }
+
+        None
+    }
+}
+
+impl Hittable for Subsurface {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        // the object is its own material, so that scattering can walk around inside the boundary
+        let mut rec = self.boundary.hit(r, ray_t)?;
+        rec.mat = Some(self);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(&self, r_in: Ray, record: &HitRecord, sampler: &mut dyn Sampler) -> Option<(Color, Ray)> {
+        let frame = Onb::new(record.normal);
+        let wo = frame.to_local(-unit_vector(r_in.direction()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+
+        // only rays that started inside, like those of a camera placed there, reach the surface
+        // from within; they just cross it
+        let eta = if record.front_face { self.refraction_index } else { 1.0 / self.refraction_index };
+        let (weight, wi) = sample_interface(&self.distribution, wo, eta, sampler)?;
+        let scattered = Ray::new(record.p, frame.to_world(wi));
+        if wi.z() > 0.0 || !record.front_face {
+            return Some((weight * Color::new(1.0, 1.0, 1.0), scattered));
+        }
+
+        let (throughput, exit) = self.random_walk(scattered, sampler)?;
+        Some((weight * throughput, exit))
+    }
+}
+
+fn transmittance(sigma_t: Color, distance: f64) -> Color {
+    Color::new((-sigma_t.x() * distance).exp(), (-sigma_t.y() * distance).exp(), (-sigma_t.z() * distance).exp())
+}
+
+fn single_scattering_albedo(albedo: f64) -> f64 {
+    // the albedo of each scattering event that makes light which scatters many times come out at
+    // `albedo` overall (Chiang et al. 2016, "Practical and Controllable Subsurface Scattering")
+    let a = albedo.clamp(0.0, 0.999);
+    let s = 4.09712 + 4.20863 * a - (9.59217 + 41.6808 * a + 17.7126 * a * a).sqrt();
+    1.0 - s * s
+}
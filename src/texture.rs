@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use crate::image::Image;
+use crate::perlin::Perlin;
+use crate::vector::{Color, Point3};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo }
+    }
+
+    pub fn gray(value: f64) -> Self {
+        // for textures that hold a single value rather than a color
+        Self::new(Color::new(value, value, value))
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3) -> Color {
+        self.albedo
+    }
+}
+
+// 3D checkerboard of two textures, alternating every `scale` units in space
+pub struct CheckerTexture {
+    inv_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { inv_scale: 1.0 / scale, even, odd }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color {
+        let x = (self.inv_scale * p.x()).floor() as i64;
+        let y = (self.inv_scale * p.y()).floor() as i64;
+        let z = (self.inv_scale * p.z()).floor() as i64;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+// marble-like veins of turbulent Perlin noise, in [0, 1]. `scale` sets how many veins fit in a unit
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f64) -> Self {
+        Self { noise: Perlin::new(), scale }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, p: &Point3) -> Color {
+        let x = 0.5 * (1.0 + (self.scale * p.z() + 10.0 * self.noise.turb(p, 7)).sin());
+        Color::new(x, x, x)
+    }
+}
+
+// sRGB encoded image looked up by texture coordinates, repeating outside [0, 1]
+pub struct ImageTexture {
+    image: Image,
+}
+
+impl ImageTexture {
+    pub fn new(filename: &str) -> Self {
+        let image = Image::load_png(filename).expect("Could not read image.");
+        Self { image }
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: &Point3) -> Color {
+        // image rows run top to bottom, v runs bottom to top
+        let x = u * self.image.width() as f64;
+        let y = (1.0 - v) * self.image.height() as f64;
+        let c = self.image.bilinear(x, y);
+
+        Color::new(srgb_to_linear(c.x()), srgb_to_linear(c.y()), srgb_to_linear(c.z()))
+    }
+}
+
+pub fn srgb_to_linear(x: f64) -> f64 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,268 @@
+use crate::aabb::Aabb;
+use crate::common::{solve_quadratic, PI};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{dot, Point3, Vec3};
+
+// ring around `axis`, with its tube's center `major_radius` from `center` and the tube
+// `minor_radius` thick. u runs around the axis and v around the tube, starting on the outside
+pub struct Torus {
+    center: Point3,
+    major_radius: f64,
+    minor_radius: f64,
+    frame: Onb,
+    mat: Box<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(center: Point3, axis: Vec3, major_radius: f64, minor_radius: f64, mat: impl Material + 'static) -> Self {
+        // everything is worked out in a frame where the axis runs along z
+        Self { center, major_radius, minor_radius, frame: Onb::new(axis), mat: Box::new(mat) }
+    }
+
+    fn surface(&self, p: Point3) -> (Vec3, (f64, f64), (Vec3, Vec3)) {
+        // outward normal, texture coordinates and tangents at a local point
+        let ring_distance = (p.x() * p.x() + p.y() * p.y()).sqrt().max(1e-12);
+        let (cos_phi, sin_phi) = (p.x() / ring_distance, p.y() / ring_distance);
+        let phi = p.y().atan2(p.x());
+        let phi = if phi < 0.0 { phi + 2.0 * PI } else { phi };
+
+        let theta = p.z().atan2(ring_distance - self.major_radius);
+        let theta = if theta < 0.0 { theta + 2.0 * PI } else { theta };
+
+        let normal = Vec3::new(theta.cos() * cos_phi, theta.cos() * sin_phi, theta.sin());
+        let dpdu = 2.0 * PI * Vec3::new(-p.y(), p.x(), 0.0);
+        let dpdv = 2.0 * PI * self.minor_radius * Vec3::new(-theta.sin() * cos_phi, -theta.sin() * sin_phi, theta.cos());
+
+        (normal, (phi / (2.0 * PI), theta / (2.0 * PI)), (dpdu, dpdv))
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let o = self.frame.to_local(r.origin() - self.center);
+        let d = self.frame.to_local(r.direction());
+
+        // the quartic is badly conditioned far from the torus, so it's solved for a unit direction
+        // from a point moved up the ray to just outside the torus's bounding sphere
+        let length = d.length();
+        let d = d / length;
+        let shift = (-dot(&o, &d) - self.major_radius - self.minor_radius).max(0.0);
+        let o = o + shift * d;
+
+        // points on the torus satisfy (|p|^2 - R^2 - r^2)^2 = 4 R^2 (r^2 - z^2)
+        let r2 = self.major_radius * self.major_radius;
+        let f = dot(&o, &d);
+        let g = dot(&o, &o) - r2 - self.minor_radius * self.minor_radius;
+        let mut roots = solve_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * g + 4.0 * r2 * d.z() * d.z(),
+            4.0 * f * g + 8.0 * r2 * o.z() * d.z(),
+            g * g + 4.0 * r2 * (o.z() * o.z() - self.minor_radius * self.minor_radius),
+        );
+
+        // the nearest hit that a cutout material doesn't let the ray through
+        roots.sort_by(|a, b| a.total_cmp(b));
+        for s in roots {
+            let t = (s + shift) / length;
+            if !ray_t.surrounds(t) {
+                continue;
+            }
+
+            let (normal, uv, (dpdu, dpdv)) = self.surface(o + s * d);
+
+            let mut rec = HitRecord::new();
+            rec.t = t;
+            rec.p = r.at(t);
+            rec.set_face_normal(r, &self.frame.to_world(normal));
+            (rec.u, rec.v) = uv;
+            (rec.dpdu, rec.dpdv) = (self.frame.to_world(dpdu), self.frame.to_world(dpdv));
+            rec.mat = Some(&(*self.mat));
+
+            if rec.is_opaque(r) {
+                return Some(rec);
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // the disk through the middle of the tube, grown by the tube's thickness
+        let axis = self.frame.to_world(Vec3::new(0.0, 0.0, 1.0));
+        let bbox = Aabb::around_disk(self.center, axis, self.major_radius);
+        let thickness = 2.0 * self.minor_radius;
+        Aabb::new(bbox.x.expand(thickness), bbox.y.expand(thickness), bbox.z.expand(thickness))
+    }
+}
+
+fn solve_quartic(b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    // real roots of t^4 + b t^3 + c t^2 + d t + e = 0, with Ferrari's method. the closed form
+    // loses precision, so each root is polished with a few steps of Newton's method
+    let shift = -b / 4.0;
+
+    // substituting t = y + shift leaves y^4 + p y^2 + q y + r = 0
+    let p = c - 3.0 * b * b / 8.0;
+    let q = d - b * c / 2.0 + b * b * b / 8.0;
+    let r = e - b * d / 4.0 + b * b * c / 16.0 - 3.0 * b.powi(4) / 256.0;
+
+    let mut ys = Vec::with_capacity(4);
+    let mut add_quadratic_roots = |b: f64, c: f64| {
+        if let Some((y0, y1)) = solve_quadratic(1.0, b, c) {
+            ys.push(y0);
+            ys.push(y1);
+        }
+    };
+
+    // pick m so that both sides of (y^2 + p/2 + m)^2 = 2m y^2 - q y + m^2 + p m + p^2/4 - r are
+    // perfect squares, then take square roots to split it into two quadratics
+    let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+    if q.abs() < 1e-12 || m <= 1e-12 {
+        // biquadratic: a quadratic in y^2
+        if let Some((z0, z1)) = solve_quadratic(1.0, p, r) {
+            for z in [z0, z1] {
+                if z >= 0.0 {
+                    ys.push(z.sqrt());
+                    ys.push(-z.sqrt());
+                }
+            }
+        }
+    } else {
+        let s = (2.0 * m).sqrt();
+        add_quadratic_roots(-s, p / 2.0 + m + q / (2.0 * s));
+        add_quadratic_roots(s, p / 2.0 + m - q / (2.0 * s));
+    }
+
+    ys.iter()
+        .map(|y| {
+            let mut t = y + shift;
+            for _ in 0..2 {
+                let f = (((t + b) * t + c) * t + d) * t + e;
+                let df = ((4.0 * t + 3.0 * b) * t + 2.0 * c) * t + d;
+                if df.abs() > 1e-12 {
+                    t -= f / df;
+                }
+            }
+            t
+        })
+        .collect()
+}
+
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    // largest real root of m^3 + a m^2 + b m + c = 0
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+
+    if r * r < q * q * q {
+        // three real roots
+        let theta = (r / (q * q * q).sqrt()).acos();
+        let scale = -2.0 * q.sqrt();
+        return [theta, theta + 2.0 * PI, theta - 2.0 * PI]
+            .iter()
+            .map(|angle| scale * (angle / 3.0).cos() - a / 3.0)
+            .fold(f64::NEG_INFINITY, f64::max);
+    }
+
+    let big_a = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+    let big_b = if big_a != 0.0 { q / big_a } else { 0.0 };
+    big_a + big_b - a / 3.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Color;
+    use crate::common::{random_f64, seed_random, INFINITY};
+    use crate::material::Lambertian;
+
+    fn ring() -> Torus {
+        // major radius 2 and minor radius 0.5 around z, so the hole is 1.5 wide
+        let white = Lambertian::new(Color::new(1.0, 1.0, 1.0));
+        Torus::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0), 2.0, 0.5, white)
+    }
+
+    fn hit_t(torus: &Torus, origin: Point3, direction: Vec3, from: f64) -> Option<f64> {
+        torus.hit(&Ray::new(origin, direction), Interval::new(from, INFINITY)).map(|rec| rec.t)
+    }
+
+    #[test]
+    fn quartic_roots_are_found_and_polished() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        let mut roots = solve_quartic(-10.0, 35.0, -50.0, 24.0);
+        roots.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip([1.0, 2.0, 3.0, 4.0]) {
+            assert!((root - expected).abs() < 1e-12, "{:?}", roots);
+        }
+
+        // t^4 + 1 has no real roots
+        assert!(solve_quartic(0.0, 0.0, 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn rays_through_the_hole_miss() {
+        let torus = ring();
+        assert_eq!(hit_t(&torus, Point3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.001), None);
+        assert_eq!(hit_t(&torus, Point3::new(0.3, -0.2, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.001), None);
+        // a slanted ray that passes the tube 0.08 away from its surface
+        assert_eq!(hit_t(&torus, Point3::new(0.0, 0.0, 5.0), Vec3::new(1.4, 0.0, -5.0), 0.001), None);
+    }
+
+    #[test]
+    fn grazing_rays_by_the_hole() {
+        let torus = ring();
+
+        // just inside the inner edge of the tube the ray slips through
+        assert_eq!(hit_t(&torus, Point3::new(1.499, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.001), None);
+
+        // just outside, it clips the tube where (x - 2)^2 + z^2 = 0.25
+        let x: f64 = 1.501;
+        let z = (0.25 - (x - 2.0) * (x - 2.0)).sqrt();
+        let t = hit_t(&torus, Point3::new(x, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), 0.001).expect("missed the tube");
+        assert!((t - (5.0 - z)).abs() < 1e-6, "{} != {}", t, 5.0 - z);
+    }
+
+    #[test]
+    fn near_and_far_roots_of_the_tube() {
+        // along the x axis the ray crosses the tube at -2.5, -1.5, 1.5 and 2.5
+        let torus = ring();
+        let origin = Point3::new(-5.0, 0.0, 0.0);
+        let direction = Vec3::new(2.0, 0.0, 0.0);
+
+        for (from, expected, front_face) in [(0.001, 2.5, true), (2.6, 3.5, false), (3.6, 6.5, true), (6.6, 7.5, false)] {
+            let rec = torus.hit(&Ray::new(origin, direction), Interval::new(from / 2.0, INFINITY)).expect("missed the tube");
+            assert!((rec.t * 2.0 - expected).abs() < 1e-9, "{} != {}", rec.t * 2.0, expected);
+            assert_eq!(rec.front_face, front_face);
+            // the normal faces back along the ray
+            assert!(dot(&rec.normal, &direction) < 0.0);
+        }
+        assert_eq!(hit_t(&torus, origin, direction, 7.6 / 2.0), None);
+    }
+
+    #[test]
+    fn hits_lie_on_the_surface() {
+        seed_random(5);
+        let center = Point3::new(1.0, -2.0, 0.5);
+        let axis = Vec3::new(1.0, 2.0, -0.5);
+        let white = Lambertian::new(Color::new(1.0, 1.0, 1.0));
+        let torus = Torus::new(center, axis, 2.0, 0.5, white);
+        let frame = Onb::new(axis);
+
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let origin = center + 10.0 * Vec3::new(random_f64() - 0.5, random_f64() - 0.5, random_f64() - 0.5);
+            let target = center + 3.0 * Vec3::new(random_f64() - 0.5, random_f64() - 0.5, random_f64() - 0.5);
+            if let Some(rec) = torus.hit(&Ray::new(origin, target - origin), Interval::new(0.001, INFINITY)) {
+                let p = frame.to_local(rec.p - center);
+                let ring_distance = (p.x() * p.x() + p.y() * p.y()).sqrt();
+                let tube_distance = ((ring_distance - 2.0).powi(2) + p.z() * p.z()).sqrt();
+                assert!((tube_distance - 0.5).abs() < 1e-9, "{} is off the surface", tube_distance);
+                hits += 1;
+            }
+        }
+        assert!(hits > 100);
+    }
+}
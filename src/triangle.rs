@@ -0,0 +1,104 @@
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{cross, dot, unit_vector, Point3, Vec3};
+
+pub struct Triangle {
+    p0: Point3,
+    p1: Point3,
+    p2: Point3,
+    uvs: [(f64, f64); 3],
+    mat: Box<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, mat: impl Material + 'static) -> Self {
+        Self::with_uvs(p0, p1, p2, [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)], mat)
+    }
+
+    pub fn with_uvs(p0: Point3, p1: Point3, p2: Point3, uvs: [(f64, f64); 3], mat: impl Material + 'static) -> Self {
+        Self { p0, p1, p2, uvs, mat: Box::new(mat) }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord<'_>> {
+        let (t, b1, b2) = intersect_triangle(r, ray_t, self.p0, self.p1, self.p2)?;
+        let b0 = 1.0 - b1 - b2;
+        let outward_normal = unit_vector(cross(self.p1 - self.p0, self.p2 - self.p0));
+
+        let mut rec = HitRecord::new();
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.set_face_normal(r, &outward_normal);
+        rec.u = b0 * self.uvs[0].0 + b1 * self.uvs[1].0 + b2 * self.uvs[2].0;
+        rec.v = b0 * self.uvs[0].1 + b1 * self.uvs[1].1 + b2 * self.uvs[2].1;
+        (rec.dpdu, rec.dpdv) = triangle_tangents([self.p0, self.p1, self.p2], self.uvs, outward_normal);
+        rec.mat = Some(&(*self.mat));
+
+        if !rec.is_opaque(r) {
+            return None;
+        }
+
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let bbox = Aabb::from_points(self.p0, self.p1);
+        Aabb::surrounding(&bbox, &Aabb::from_points(self.p2, self.p2))
+    }
+}
+
+pub fn intersect_triangle(r: &Ray, ray_t: Interval, p0: Point3, p1: Point3, p2: Point3) -> Option<(f64, f64, f64)> {
+    // möller-trumbore: solve for the distance and the barycentric coordinates of p1 and p2 at once
+    let e1 = p1 - p0;
+    let e2 = p2 - p0;
+
+    let pvec = cross(r.direction(), e2);
+    let det = dot(&e1, &pvec);
+    if det.abs() < 1e-12 {
+        // the ray runs parallel to the triangle
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - p0;
+    let b1 = dot(&tvec, &pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = cross(tvec, e1);
+    let b2 = dot(&r.direction(), &qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = dot(&e2, &qvec) * inv_det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+pub fn triangle_tangents(p: [Point3; 3], uvs: [(f64, f64); 3], normal: Vec3) -> (Vec3, Vec3) {
+    // solve for the derivatives of the point with respect to the texture coordinates
+    let (du02, dv02) = (uvs[0].0 - uvs[2].0, uvs[0].1 - uvs[2].1);
+    let (du12, dv12) = (uvs[1].0 - uvs[2].0, uvs[1].1 - uvs[2].1);
+    let dp02 = p[0] - p[2];
+    let dp12 = p[1] - p[2];
+
+    let determinant = du02 * dv12 - dv02 * du12;
+    if determinant.abs() < 1e-12 {
+        // degenerate texture coordinates; any tangent frame will do
+        let frame = Onb::new(normal);
+        return (frame.to_world(Vec3::new(1.0, 0.0, 0.0)), frame.to_world(Vec3::new(0.0, 1.0, 0.0)));
+    }
+
+    let inv_det = 1.0 / determinant;
+    ((dv12 * dp02 - dv02 * dp12) * inv_det, (du02 * dp12 - du12 * dp02) * inv_det)
+}
@@ -1,8 +1,7 @@
-use std::ops::{Add, Div, Mul, Neg, Sub};
-use std::fs::File;
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 use std::io::Write;
 
-use crate::common::{random_f64, random_range_f64};
+use crate::common::{random_f64, random_range_f64, PI};
 use crate::interval::Interval;
 
 
@@ -51,7 +50,7 @@ impl Vec3 {
         Vec3::new(random_range_f64(min, max), random_range_f64(min, max), random_range_f64(min, max))
     }
 
-    pub fn write(&self, f: &mut File) -> () {
+    pub fn write(&self, f: &mut impl Write) -> () {
         let mut r = self.x();
         let mut g = self.y();
         let mut b = self.z();
@@ -71,6 +70,14 @@ impl Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f64;
+
+    fn index(&self, i: usize) -> &f64 {
+        &self.e[i]
+    }
+}
+
 impl Neg for Vec3 {
     type Output = Self;
 
@@ -215,30 +222,35 @@ pub fn unit_vector(v: Vec3) -> Vec3 {
     v / v.length()
 }
 
-pub fn random_in_unit_disk() -> Vec3 {
-    loop {
-        let p = Vec3::new(random_range_f64(-1.0, 1.0), random_range_f64(-1.0, 1.0), 0.0);
-        if p.length_squared() < 1.0 {
-            return p;
-        }
+pub fn sample_unit_disk(u: (f64, f64)) -> Vec3 {
+    // maps a point in the unit square onto the unit disk with Shirley's concentric mapping, which
+    // keeps stratified samples stratified
+    let a = 2.0 * u.0 - 1.0;
+    let b = 2.0 * u.1 - 1.0;
+    if a == 0.0 && b == 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
     }
-}
 
-pub fn random_in_unit_sphere() -> Vec3 {
-    loop {
-        let p = Vec3::random_range(-1.0, 1.0);
-        if p.length_squared() < 1.0 {
-            return p;
-        }
-    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, (PI / 4.0) * (b / a))
+    } else {
+        (b, PI / 2.0 - (PI / 4.0) * (a / b))
+    };
+
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
 }
 
-pub fn random_unit_vector() -> Vec3 {
-    unit_vector(random_in_unit_sphere())
+pub fn sample_unit_vector(u: (f64, f64)) -> Vec3 {
+    // maps a point in the unit square to a uniformly distributed direction
+    let z = 1.0 - 2.0 * u.0;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u.1;
+
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
 
 pub fn random_on_hemisphere(normal: Vec3) -> Vec3 {
-    let on_unit_sphere = random_unit_vector();
+    let on_unit_sphere = sample_unit_vector((random_f64(), random_f64()));
     if dot(&on_unit_sphere, &normal)  > 0.0 {
         return on_unit_sphere;
     } 
@@ -266,5 +278,10 @@ pub fn linear_to_gamma(linear_component: f64) -> f64 {
     return 0.0;
 }
 
+pub fn luminance(c: Color) -> f64 {
+    // relative luminance of a linear sRGB color
+    0.2126 * c.x() + 0.7152 * c.y() + 0.0722 * c.z()
+}
+
 pub type Point3 = Vec3;
 pub type Color = Vec3;